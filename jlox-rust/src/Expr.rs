@@ -1,13 +1,29 @@
 // NOT ACTUALLY GENERATED
 // but theoretically, generated by bin/generate_ast.rs
 use std::fmt;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
 use crate::token::Token;
+use crate::lox_callable::NativeFunction;
+use crate::lox_function::LoxFunction;
+use crate::lox_class::LoxClass;
+use crate::lox_instance::LoxInstance;
+use crate::lox_trait::LoxTrait;
+use crate::lox_module::LoxModule;
+use crate::stmt::FunctionDecl;
 
+// Variable, Assign, This and Super carry a depth: how many environments out from
+// the current one their name lives. the resolver fills it in, where jlox's keeps
+// it in a map in the interpreter (Resolver.resolveLocal: jlox/Resolver.java L68)
 #[derive(Debug, Clone)]
 pub enum Expr {
     Assign {
 	name: Token,
-	value: Box<Expr>
+	value: Box<Expr>,
+	depth: Cell<Option<usize>>,
     },
     Binary {
 	left: Box<Expr>,
@@ -16,12 +32,89 @@ pub enum Expr {
 	operator: Token,
 	right: Box<Expr>,
     },
+    Call {
+	callee: Box<Expr>,
+	// closing paren, for the line number in errors
+	paren: Token,
+	arguments: Vec<Expr>,
+	// f(a, b: 2): the ones passed by parameter name, which come after the others
+	named: Vec<(Token, Expr)>,
+    },
+    // target += value, and ++/-- (value is then 1)
+    // the target is a Variable, an Index or a Get, evaluated once
+    CompoundAssign {
+	target: Box<Expr>,
+	// Plus, Minus, Star, Slash or Percent; the lexeme is what was written
+	operator: Token,
+	value: Box<Expr>,
+	// x++ is the old value, ++x and x += 1 are the new one
+	postfix: bool,
+    },
+    // left, right: evaluates both, and is the right
+    Comma {
+	left: Box<Expr>,
+	right: Box<Expr>,
+    },
+    // object.name
+    Get {
+	object: Box<Expr>,
+	name: Token,
+    },
     Grouping {
 	expression: Box<Expr>,
     },
+    // list[index]
+    Index {
+	object: Box<Expr>,
+	// closing bracket, for the line number in errors
+	bracket: Token,
+	index: Box<Expr>,
+    },
+    List {
+	elements: Vec<Expr>,
+    },
+    // fun (a, b) { ... } or (a, b) => ...
+    Lambda {
+	declaration: Rc<FunctionDecl>,
+    },
     Literal {
 	value: Value,
     },
+    // {key: value, ...}
+    Map {
+	// opening brace, for the line number in errors
+	brace: Token,
+	entries: Vec<(Expr, Expr)>,
+    },
+    // object.name = value
+    Set {
+	object: Box<Expr>,
+	name: Token,
+	value: Box<Expr>,
+    },
+    // list[index] = value
+    SetIndex {
+	object: Box<Expr>,
+	bracket: Token,
+	index: Box<Expr>,
+	value: Box<Expr>,
+    },
+    // super.method
+    Super {
+	keyword: Token,
+	method: Token,
+	depth: Cell<Option<usize>>,
+    },
+    // condition ? then_branch : else_branch
+    Ternary {
+	condition: Box<Expr>,
+	then_branch: Box<Expr>,
+	else_branch: Box<Expr>,
+    },
+    This {
+	keyword: Token,
+	depth: Cell<Option<usize>>,
+    },
     Unary {
 	// there's a small of tokens that can be unary operators - just Minus and Bang
 	operator: Token,
@@ -29,38 +122,213 @@ pub enum Expr {
     },
     Variable {
 	name: Token,
+	depth: Cell<Option<usize>>,
     }
 }
 
 pub trait Visitor<T> {
-    fn visit_assignment(&mut self, name: &Token, value: &Expr) -> T;
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, depth: &Cell<Option<usize>>) -> T;
     fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> T;
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr], named: &[(Token, Expr)]) -> T;
+    fn visit_comma(&mut self, left: &Expr, right: &Expr) -> T;
+    fn visit_compound_assign(&mut self, target: &Expr, operator: &Token, value: &Expr, postfix: bool) -> T;
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> T;
     fn visit_grouping(&mut self, expression: &Expr) -> T;
+    fn visit_index(&mut self, object: &Expr, bracket: &Token, index: &Expr) -> T;
+    fn visit_list(&mut self, elements: &[Expr]) -> T;
+    fn visit_lambda(&mut self, declaration: &Rc<FunctionDecl>) -> T;
     fn visit_literal(&self, value: &Value) -> T;
+    fn visit_map(&mut self, brace: &Token, entries: &[(Expr, Expr)]) -> T;
+    fn visit_set(&mut self, object: &Expr, name: &Token, value: &Expr) -> T;
+    fn visit_set_index(&mut self, object: &Expr, bracket: &Token, index: &Expr, value: &Expr) -> T;
+    fn visit_super(&mut self, keyword: &Token, method: &Token, depth: &Cell<Option<usize>>) -> T;
+    fn visit_ternary(&mut self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> T;
+    fn visit_this(&mut self, keyword: &Token, depth: &Cell<Option<usize>>) -> T;
     fn visit_unary(&mut self, operator: &Token, right: &Expr) -> T;
-    fn visit_variable(&self, name: &Token) -> T;
+    fn visit_variable(&mut self, name: &Token, depth: &Cell<Option<usize>>) -> T;
 }
 
 impl Expr {
     pub fn accept<T, V: Visitor<T>>(&self, visitor: &mut V) -> T {
 	use crate::expr::Expr::*;
 	match self {
-	    Assign { name, value } => visitor.visit_assignment(name, value),
+	    Assign { name, value, depth } => visitor.visit_assignment(name, value, depth),
 	    Binary {left, operator, right} => visitor.visit_binary(left, operator, right),
+	    Call { callee, paren, arguments, named } => visitor.visit_call(callee, paren, arguments, named),
+	    Comma { left, right } => visitor.visit_comma(left, right),
+	    CompoundAssign { target, operator, value, postfix } => visitor.visit_compound_assign(target, operator, value, *postfix),
+	    Get { object, name } => visitor.visit_get(object, name),
 	    Grouping { expression } => visitor.visit_grouping(expression),
+	    Index { object, bracket, index } => visitor.visit_index(object, bracket, index),
+	    List { elements } => visitor.visit_list(elements),
+	    Lambda { declaration } => visitor.visit_lambda(declaration),
 	    Literal { value }=> visitor.visit_literal(value),
+	    Map { brace, entries } => visitor.visit_map(brace, entries),
+	    Set { object, name, value } => visitor.visit_set(object, name, value),
+	    SetIndex { object, bracket, index, value } => visitor.visit_set_index(object, bracket, index, value),
+	    Super { keyword, method, depth } => visitor.visit_super(keyword, method, depth),
+	    Ternary { condition, then_branch, else_branch } => visitor.visit_ternary(condition, then_branch, else_branch),
+	    This { keyword, depth } => visitor.visit_this(keyword, depth),
 	    Unary { operator, right } => visitor.visit_unary(operator, right),
-	    Variable { name } => visitor.visit_variable(name),
+	    Variable { name, depth } => visitor.visit_variable(name, depth),
 	}
     }
 }
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Value {
     False,
     True,
     Nil,
     Number(f64),
-    Strng(String)
+    Strng(String),
+    Native(NativeFunction),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    Trait(Rc<LoxTrait>),
+    Module(Rc<LoxModule>),
+    // shared and mutable, like an object in jlox
+    List(Rc<Contents<Vec<Value>>>),
+    Map(Rc<Contents<BTreeMap<MapKey, Value>>>),
+    // range(start, end), counted out one number at a time by a for-in loop
+    Range(f64, f64)
+}
+
+// a list's or map's storage. dropping a list drops the values in it, which can be
+// lists too, so a deeply nested one would recurse a level at a time on the rust
+// stack. instead its drop takes out the lists and maps inside it no one else holds
+// and empties them itself, one at a time
+pub struct Contents<T: HasValues>(RefCell<T>);
+
+pub trait HasValues: Default {
+    fn into_values(self) -> Vec<Value>;
+}
+
+impl HasValues for Vec<Value> {
+    fn into_values(self) -> Vec<Value> {
+	self
+    }
+}
+
+impl HasValues for BTreeMap<MapKey, Value> {
+    fn into_values(self) -> Vec<Value> {
+	BTreeMap::into_values(self).collect()
+    }
+}
+
+impl<T: HasValues> Contents<T> {
+    pub fn new(values: T) -> Contents<T> {
+	Contents(RefCell::new(values))
+    }
+}
+
+impl<T: HasValues> Deref for Contents<T> {
+    type Target = RefCell<T>;
+
+    fn deref(&self) -> &RefCell<T> {
+	&self.0
+    }
+}
+
+impl<T: HasValues> Drop for Contents<T> {
+    fn drop(&mut self) {
+	let mut values = self.0.take().into_values();
+	while let Some(value) = values.pop() {
+	    // one that's still shared elsewhere isn't dropped here, so it's left alone
+	    match value {
+		Value::List(items) => if let Ok(items) = Rc::try_unwrap(items) {
+		    values.extend(items.0.take().into_values());
+		},
+		Value::Map(entries) => if let Ok(entries) = Rc::try_unwrap(entries) {
+		    values.extend(entries.0.take().into_values());
+		},
+		_ => {}
+	    }
+	}
+    }
+}
+
+impl<T: HasValues + fmt::Debug> fmt::Debug for Contents<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	self.0.fmt(f)
+    }
+}
+
+// the values that can be map keys
+// a BTreeMap needs a total order, which f64 doesn't have, so numbers are
+// checked on the way in: NaN is refused, since it isn't even equal to itself,
+// and -0 is stored as 0, since -0 == 0
+#[derive(Debug, Clone)]
+pub enum MapKey {
+    Number(f64),
+    Strng(String),
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Result<MapKey, String> {
+	match value {
+	    Value::Number(n) if n.is_nan() => Err("NaN can't be a map key.".to_string()),
+	    Value::Number(n) if *n == 0.0 => Ok(MapKey::Number(0.0)),
+	    Value::Number(n) => Ok(MapKey::Number(*n)),
+	    Value::Strng(s) => Ok(MapKey::Strng(s.clone())),
+	    _ => Err("Map keys must be numbers or strings.".to_string())
+	}
+    }
+
+    pub fn to_value(&self) -> Value {
+	match self {
+	    MapKey::Number(n) => Value::Number(*n),
+	    MapKey::Strng(s) => Value::Strng(s.clone()),
+	}
+    }
+}
+
+// numbers sort before strings
+impl Ord for MapKey {
+    fn cmp(&self, other: &MapKey) -> Ordering {
+	match (self, other) {
+	    (MapKey::Number(a), MapKey::Number(b)) => a.total_cmp(b),
+	    (MapKey::Number(_), MapKey::Strng(_)) => Ordering::Less,
+	    (MapKey::Strng(_), MapKey::Number(_)) => Ordering::Greater,
+	    (MapKey::Strng(a), MapKey::Strng(b)) => a.cmp(b),
+	}
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &MapKey) -> Option<Ordering> {
+	Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &MapKey) -> bool {
+	self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MapKey {}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+	use Value::*;
+	match (self, other) {
+	    (Nil, Nil) | (True, True) | (False, False) => true,
+	    (Number(a), Number(b)) => a == b,
+	    (Strng(a), Strng(b)) => a == b,
+	    (Native(a), Native(b)) => a == b,
+	    (Function(a), Function(b)) => Rc::ptr_eq(a, b),
+	    (Class(a), Class(b)) => Rc::ptr_eq(a, b),
+	    (Instance(a), Instance(b)) => Rc::ptr_eq(a, b),
+	    (Trait(a), Trait(b)) => Rc::ptr_eq(a, b),
+	    (Module(a), Module(b)) => Rc::ptr_eq(a, b),
+	    // lists and maps are objects, so they're only equal to themselves
+	    (List(a), List(b)) => Rc::ptr_eq(a, b),
+	    (Map(a), Map(b)) => Rc::ptr_eq(a, b),
+	    (Range(a, b), Range(c, d)) => a == c && b == d,
+	    _ => false
+	}
+    }
 }
 
 impl From<bool> for Value {
@@ -84,15 +352,95 @@ impl From<String> for Value {
     }
 }
 
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Value {
+	Value::List(Rc::new(Contents::new(items)))
+    }
+}
+
+impl From<BTreeMap<MapKey, Value>> for Value {
+    fn from(entries: BTreeMap<MapKey, Value>) -> Value {
+	Value::Map(Rc::new(Contents::new(entries)))
+    }
+}
+
+// printing and writing json recurse once per list or map a value is nested in,
+// so past this they stop with an error instead of running out of stack
+pub const MAX_NESTING: usize = 1000;
+
+// lists and maps nested deeper than MAX_NESTING are cut off with ..., which print
+// reports as an error instead
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	let mut out = String::new();
+	if self.write(&mut out, &mut vec![]).is_err() {
+	    out.push_str("...");
+	}
+	f.write_str(&out)
+    }
+}
+
+impl Value {
+    // how print shows a value, or None when it's nested too deeply to print
+    pub fn printed(&self) -> Option<String> {
+	let mut out = String::new();
+	self.write(&mut out, &mut vec![]).ok().map(|_| out)
+    }
+
+    // `seen` holds the lists and maps we're in the middle of printing,
+    // so one that contains itself comes out as [...] or {...} instead of forever
+    fn write(&self, f: &mut impl fmt::Write, seen: &mut Vec<*const ()>) -> fmt::Result {
 	use Value::*;
 	match self {
 	    Nil => write!(f, "nil"),
 	    True => write!(f, "true"),
 	    False => write!(f, "false"),
 	    Number(n) => write!(f, "{}", n),
-	    Strng(s) => write!(f, "{}", s)
+	    Strng(s) => write!(f, "{}", s),
+	    Native(_) => write!(f, "<native fn>"),
+	    Function(function) => write!(f, "{}", function),
+	    // LoxClass.toString and LoxInstance.toString: jlox/LoxClass.java L51, jlox/LoxInstance.java L32
+	    Class(class) => write!(f, "{}", class.name),
+	    Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+	    Trait(t) => write!(f, "{:?}", t),
+	    Module(module) => write!(f, "{:?}", module),
+	    List(items) => {
+		let ptr = Rc::as_ptr(items) as *const ();
+		if seen.contains(&ptr) { return write!(f, "[...]"); }
+		if seen.len() >= MAX_NESTING { return Err(fmt::Error); }
+		seen.push(ptr);
+		write!(f, "[")?;
+		for (i, item) in items.borrow().iter().enumerate() {
+		    if i > 0 { write!(f, ", ")?; }
+		    item.write_element(f, seen)?;
+		}
+		seen.pop();
+		write!(f, "]")
+	    },
+	    Map(entries) => {
+		let ptr = Rc::as_ptr(entries) as *const ();
+		if seen.contains(&ptr) { return write!(f, "{{...}}"); }
+		if seen.len() >= MAX_NESTING { return Err(fmt::Error); }
+		seen.push(ptr);
+		write!(f, "{{")?;
+		for (i, (key, value)) in entries.borrow().iter().enumerate() {
+		    if i > 0 { write!(f, ", ")?; }
+		    key.to_value().write_element(f, seen)?;
+		    write!(f, ": ")?;
+		    value.write_element(f, seen)?;
+		}
+		seen.pop();
+		write!(f, "}}")
+	    },
+	    Range(start, end) => write!(f, "range({}, {})", start, end)
+	}
+    }
+
+    // inside a list or map, strings are quoted, or ["a, b"] and ["a", "b"] look the same
+    fn write_element(&self, f: &mut impl fmt::Write, seen: &mut Vec<*const ()>) -> fmt::Result {
+	match self {
+	    Value::Strng(s) => write!(f, "\"{}\"", s),
+	    _ => self.write(f, seen)
 	}
     }
 }
@@ -3,6 +3,9 @@ use crate::token::*;
 use crate::expr::*;
 use crate::expr::Expr::*;
 use crate::token_type::TokenType;
+use crate::stmt::FunctionDecl;
+use std::rc::Rc;
+use std::cell::Cell;
 
 fn main() {
     let left = Unary {
@@ -49,10 +52,31 @@ impl Visitor<String> for AstPrinter {
 	self.parenthesize(&operator.lexeme, vec![left, right])
     }
 
+    fn visit_comma(&self, left: &Expr, right: &Expr) -> String {
+	self.parenthesize(",", vec![left, right])
+    }
+
+    fn visit_compound_assign(&self, target: &Expr, operator: &Token, value: &Expr, postfix: bool) -> String {
+	if postfix {
+	    self.parenthesize(&format!("post{}", operator.lexeme), vec![target])
+	} else {
+	    self.parenthesize(&operator.lexeme, vec![target, value])
+	}
+    }
+
+    fn visit_get(&self, object: &Expr, name: &Token) -> String {
+	self.parenthesize(&format!(".{}", name.lexeme), vec![object])
+    }
+
     fn visit_grouping(&self, expression: &Expr) -> String {
 	self.parenthesize("group", vec![expression])
     }
 
+    fn visit_lambda(&self, declaration: &Rc<FunctionDecl>) -> String {
+	let params: Vec<&str> = declaration.params.iter().map(|param| param.name.lexeme.as_str()).collect();
+	format!("(fun ({}) ...)", params.join(" "))
+    }
+
     fn visit_literal(&self, val: &Value) -> String {
 	match val {
 	    Value::False => "false".to_string(),
@@ -60,9 +84,28 @@ impl Visitor<String> for AstPrinter {
 	    Value::Nil => "nil".to_string(),
 	    Value::Number(n) => format!("{}", n),
 	    Value::Strng(s) => s.to_string(),
+	    Value::Native(_) => "<native fn>".to_string(),
+	    Value::Function(function) => format!("{}", function),
+	    Value::Class(_) | Value::Instance(_) | Value::Trait(_) | Value::Module(_) | Value::List(_) | Value::Map(_) | Value::Range(..) => format!("{}", val),
 	}
     }
 
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> String {
+	self.parenthesize(&format!("={}", name.lexeme), vec![object, value])
+    }
+
+    fn visit_super(&self, _keyword: &Token, method: &Token, _depth: &Cell<Option<usize>>) -> String {
+	format!("super.{}", method.lexeme)
+    }
+
+    fn visit_ternary(&self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> String {
+	self.parenthesize("?:", vec![condition, then_branch, else_branch])
+    }
+
+    fn visit_this(&self, _keyword: &Token, _depth: &Cell<Option<usize>>) -> String {
+	"this".to_string()
+    }
+
     fn visit_unary(&self, operator: &Token, right: &Expr) -> String {
 	self.parenthesize(&operator.lexeme, vec![right])
     }
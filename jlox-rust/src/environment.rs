@@ -2,13 +2,17 @@
 
 use crate::expr::Value;
 use crate::token::Token;
-use std::collections::HashMap;
+use crate::runtime_error::RuntimeError;
+use crate::lox_callable::{NativeFunction, NativeError};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::cell::RefCell;
 
 #[derive(Debug)]
 pub struct Environment {
     values: HashMap<String, Value>,
+    // the names in values declared with `const`
+    constants: HashSet<String>,
     enclosing: Option<Rc<RefCell<Environment>>>
 }
 
@@ -25,6 +29,7 @@ impl Environment {
   //      println!("Creating new environment");
         Environment {
             values: HashMap::new(),
+            constants: HashSet::new(),
             enclosing: None
         }
     }
@@ -33,35 +38,101 @@ impl Environment {
 //        println!("Creating environment from another");
         Environment {
             values: HashMap::new(),
+            constants: HashSet::new(),
             enclosing: Some(Rc::clone(enclosing)),
         }
     }
 
-    pub fn get(&self, name: &Token) -> Value {
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
         if let Some(value) = self.values.get(&name.lexeme) {
-            value.clone()
+            Ok(value.clone())
         } else {
             match &self.enclosing {
                 Some(enclosing) => enclosing.borrow().get(name),
-                None => panic!("Undefined variable '{}'.", name.lexeme)
+                None => Err(RuntimeError::new(name, &format!("Undefined variable '{}'.", name.lexeme)))
             }
         }
     }
 
+    // Environment.getAt: jlox/Environment.java L45
+    // distance is how many environments out the resolver found name in. a global is
+    // resolved to the outermost scope it saw, and the natives a module's globals
+    // enclose are further out still, so past that it looks the rest of the way up
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) | (_, None) => self.get(name),
+            (_, Some(enclosing)) => enclosing.borrow().get_at(distance - 1, name),
+        }
+    }
+
+    // get_here, distance environments out
+    pub fn get_here_at(&self, distance: usize, name: &str) -> Option<Value> {
+        match (distance, &self.enclosing) {
+            (0, _) | (_, None) => self.get_here(name),
+            (_, Some(enclosing)) => enclosing.borrow().get_here_at(distance - 1, name),
+        }
+    }
+
+    // like get, for names the interpreter looks up itself, e.g. `this`
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().lookup(name)),
+        }
+    }
+
+    // only this scope's, not the enclosing ones
+    pub fn get_here(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    // for names the interpreter defines itself, which are never constants
     pub fn define(&mut self, name: &str, value: Value) {
         self.values.insert(name.to_owned(), value);
     }
 
-    pub fn assign(&mut self, name: &Token, value: Value) {
-        if self.values.contains_key(&name.lexeme) {
+    // a declaration in the script. like assign, it can't replace a constant, even
+    // with another var, const, fun or class of the same name in the same scope
+    pub fn declare(&mut self, name: &Token, value: Value, constant: bool) -> Result<(), RuntimeError> {
+        if self.constants.contains(&name.lexeme) {
+            return Err(RuntimeError::new(name, &format!("Cannot assign to constant '{}'.", name.lexeme)));
+        }
+        self.values.insert(name.lexeme.to_owned(), value);
+        if constant {
+            self.constants.insert(name.lexeme.to_owned());
+        }
+        Ok(())
+    }
+
+    pub fn define_native(&mut self, name: &'static str, arity: usize, function: fn(&[Value]) -> Result<Value, NativeError>) {
+        self.define(name, Value::Native(NativeFunction { name, arity, function }));
+    }
+
+    // just this scope's, not the enclosing ones
+    pub fn names(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+
+    // Environment.assignAt: jlox/Environment.java L49
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) | (_, None) => self.assign(name, value),
+            (_, Some(enclosing)) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if self.constants.contains(&name.lexeme) {
+            Err(RuntimeError::new(name, &format!("Cannot assign to constant '{}'.", name.lexeme)))
+        } else if self.values.contains_key(&name.lexeme) {
             // have to copy the string for the hashmap
             self.values.insert(name.lexeme.to_owned(), value);
-            return;
+            Ok(())
         } else {
             if let Some(enclosing) = &self.enclosing {
-                enclosing.borrow_mut().assign(&name, value);
+                enclosing.borrow_mut().assign(&name, value)
             } else {
-                panic!("Undefined variable '{}'.", name.lexeme); 
+                Err(RuntimeError::new(name, &format!("Undefined variable '{}'.", name.lexeme)))
             }
         }
     }
@@ -1,136 +1,715 @@
 use crate::expr;
-use expr::{Expr, Value};
+use expr::{Expr, Value, MapKey};
 use crate::stmt;
-use crate::stmt::Stmt;
+use crate::stmt::{Stmt, FunctionDecl, MatchArm, Pattern, Method, MethodKind, Imported};
+use crate::lox_function::LoxFunction;
+use crate::lox_class::LoxClass;
+use crate::lox_instance::LoxInstance;
+use crate::lox_trait::LoxTrait;
+use crate::lox_callable::NativeError;
+use crate::lox_module::LoxModule;
 use crate::token::Token;
 use crate::token_type::TokenType;
 use crate::environment::Environment;
+use crate::runtime_error::{RuntimeError, ErrorKind, CallFrame};
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+// budgets for running untrusted scripts, all off by default
+// each one runs out with its own ErrorKind
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    // statements executed per call to interpret
+    pub max_steps: Option<u64>,
+    pub max_call_depth: Option<usize>,
+    pub deadline: Option<Instant>,
+    // set from another thread to stop the script at the next statement
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+// how deep evaluate and execute can recurse before "Stack overflow."
+// a level is at most ~1.4KB of stack in a debug build, so this leaves plenty of
+// the main thread's 8MB to spare
+pub const DEFAULT_MAX_DEPTH: usize = 3000;
+
+// a call goes through a lot more rust frames than one level of expression,
+// ~6KB in a debug build, so it counts as this many levels on top
+const CALL_DEPTH: usize = 3;
+
+// and so does a for-in loop while its body runs: recursing through one takes ~27KB
+// a level in a debug build, where a call alone takes ~16KB
+const FOR_IN_DEPTH: usize = 4;
+
+// what a catch clause gets for a runtime error, with its message and line filled in.
+// scripts can throw their own, or subclass it
+const ERROR_CLASS: &str = "class Error { init(message) { this.message = message; this.line = nil; } }";
+
+// why a statement stopped early. java unwinds break, continue and return with exceptions;
+// here they ride up through execute's Result next to errors, until a loop or call handles them
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Break,
+    Continue,
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Unwind {
+	Unwind::Error(error)
+    }
+}
 
 pub struct Interpreter {
-    environment: Rc<RefCell<Environment>>
+    environment: Rc<RefCell<Environment>>,
+    limits: Limits,
+    steps: u64,
+    call_depth: usize,
+    frames: Vec<CallFrame>,
+    depth: usize,
+    pub max_depth: usize,
+    // division and modulo by zero: a runtime error, or the IEEE infinity or NaN
+    strict_division: bool,
+    // for parsing imported modules the way the script was
+    pub max_parse_depth: usize,
+    pub strict: bool,
+    // for giving imported modules the same natives as the script
+    allow_io: bool,
+    args: Value,
+    error_class: Rc<LoxClass>,
+    // every module imported so far, by canonical path, so each only runs once
+    modules: HashMap<PathBuf, Rc<LoxModule>>,
+    // modules that had errors, so importing one again doesn't run it again
+    failed_modules: HashSet<PathBuf>,
+    // the script and the modules being imported right now, outermost first,
+    // for paths relative to the importing file and for catching import cycles
+    importing: Vec<PathBuf>,
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
+	let args = Value::from(vec![]);
+	let error_class = Interpreter::error_class();
+	let globals = Interpreter::natives(true, &args, &error_class);
+
 	Interpreter {
-	    environment: Rc::new(RefCell::new(Environment::new()))
+	    environment: Rc::new(RefCell::new(globals)),
+	    limits: Limits::default(),
+	    steps: 0,
+	    call_depth: 0,
+	    frames: vec![],
+	    depth: 0,
+	    max_depth: DEFAULT_MAX_DEPTH,
+	    strict_division: false,
+	    max_parse_depth: crate::parser::DEFAULT_MAX_DEPTH,
+	    strict: false,
+	    allow_io: true,
+	    args,
+	    error_class,
+	    modules: HashMap::new(),
+	    failed_modules: HashSet::new(),
+	    importing: vec![],
 	}
     }
 
-    pub fn interpret(&mut self, stmts: Vec<Stmt>) {
+    // a global environment with just the natives in it
+    fn natives(allow_io: bool, args: &Value, error_class: &Rc<LoxClass>) -> Environment {
+	let mut globals = Environment::new();
+	crate::math::define_natives(&mut globals);
+	crate::strings::define_natives(&mut globals);
+	crate::lists::define_natives(&mut globals);
+	crate::maps::define_natives(&mut globals);
+	crate::io::define_natives(&mut globals, allow_io);
+	globals.define("args", args.clone());
+	globals.define("Error", Value::Class(Rc::clone(error_class)));
+	#[cfg(feature = "serde")]
+	crate::json::define_natives(&mut globals);
+	globals
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+	self.limits = limits;
+    }
+
+    // file, stdin, environment and exit natives are on unless this switches them off
+    pub fn allow_io(&mut self, allowed: bool) {
+	self.allow_io = allowed;
+	crate::io::define_natives(&mut self.environment.borrow_mut(), allowed);
+    }
+
+    // off by default, so 1 / 0 is infinity like in jlox
+    pub fn strict_division(&mut self, strict: bool) {
+	self.strict_division = strict;
+    }
+
+    // the names defined in the global scope: natives, and whatever earlier runs declared
+    pub fn global_names(&self) -> Vec<String> {
+	self.environment.borrow().names()
+    }
+
+    // command line arguments after the script, as the global list `args`
+    pub fn set_args(&mut self, args: Vec<String>) {
+	let args = args.into_iter().map(Value::from).collect::<Vec<Value>>();
+	self.args = Value::from(args);
+	self.environment.borrow_mut().define("args", self.args.clone());
+    }
+
+    // the file being run, which the script's imports are relative to
+    pub fn set_script(&mut self, path: &str) {
+	let path = Path::new(path);
+	self.importing.push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+    }
+
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), RuntimeError> {
+	self.steps = 0;
+	self.call_depth = 0;
+	self.frames.clear();
+	self.depth = 0;
 	for stmt in stmts {
-	    self.execute(&stmt);
+	    // the parser doesn't let break, continue or return get this far
+	    if let Err(Unwind::Error(error)) = self.execute(&stmt) {
+		return Err(error);
+	    }
 	}
+	Ok(())
     }
 
-    fn execute(&mut self, stmt: &Stmt) {
-	stmt.accept(self);
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+	self.check_budget()?;
+	self.nest()?;
+	let result = stmt.accept(self);
+	self.depth -= 1;
+	result
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> Value {
-	expr.accept(self)
+    // Interpreter.executeBlock: jlox/Interpreter.java L91
+    // runs stmts in environment, then puts the current one back
+    pub fn execute_block(&mut self, stmts: &[Stmt], environment: Rc<RefCell<Environment>>) -> Result<(), Unwind> {
+	let prev = std::mem::replace(&mut self.environment, environment);
+	let mut result = Ok(());
+	for stmt in stmts.iter() {
+	    result = self.execute(stmt);
+	    if result.is_err() { break; }
+	}
+	// restore the environment even when unwinding, like java's finally
+	self.environment = prev;
+	result
     }
 
-    fn is_truthy(&self, val: Value) -> bool {
-	use Value::*;
-	match val {
-	    False => false,
-	    True => true,
-	    Nil => false,
-	    Number(_) => true,
-	    Strng(_) => true,
+    // evaluates expr in environment, then puts the current one back
+    pub fn evaluate_in(&mut self, expr: &Expr, environment: Rc<RefCell<Environment>>) -> Result<Value, RuntimeError> {
+	let prev = std::mem::replace(&mut self.environment, environment);
+	let result = self.evaluate(expr);
+	self.environment = prev;
+	result
+    }
+
+    // what a catch clause gets: the thrown value, or an Error with a runtime error's message and line
+    fn caught(&self, error: &RuntimeError) -> Value {
+	if let Some(value) = &error.value {
+	    return value.clone();
 	}
+	let mut instance = LoxInstance::new(Rc::clone(&self.error_class));
+	instance.fields.insert("message".to_string(), Value::from(error.message.clone()));
+	let line = error.line.map(|line| Value::from(line as f64)).unwrap_or(Value::Nil);
+	instance.fields.insert("line".to_string(), line);
+	Value::Instance(Rc::new(RefCell::new(instance)))
     }
 
-    fn is_equal(&self, a: Value, b: Value) -> bool {
-	a == b
+    // the Error class, from its lox source. its methods close over an empty
+    // environment, since they don't use any globals
+    fn error_class() -> Rc<LoxClass> {
+	let stmts = crate::lox::compile(ERROR_CLASS.to_string(), crate::parser::DEFAULT_MAX_DEPTH, false, vec![])
+	    .expect("the Error class should compile");
+	match &stmts[0] {
+	    Stmt::Class { methods, .. } => {
+		let closure = Rc::new(RefCell::new(Environment::new()));
+		let [methods, getters, setters, statics] = Interpreter::method_tables(methods, &closure);
+		Rc::new(LoxClass { name: "Error".to_string(), superclass: None, methods, getters, setters, statics })
+	    },
+	    _ => unreachable!("the Error source is a class declaration"),
+	}
     }
 
-    fn numeric(&self, operand: Value) -> f64 {
-	match operand {
-	    Value::Number(n) => n,
-	    _ => panic!("operand must be a number")
+    // a function value closing over the current environment
+    fn function(&self, declaration: &Rc<FunctionDecl>) -> Value {
+	Value::Function(Rc::new(LoxFunction {
+	    declaration: Rc::clone(declaration),
+	    closure: Rc::clone(&self.environment),
+	    is_initializer: false,
+	}))
+    }
+
+    // calls a function, native or class with arguments that are already evaluated
+    fn call_value(&mut self, paren: &Token, callee: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+	self.call_value_named(paren, callee, args, vec![])
+    }
+
+    // named are the arguments passed by parameter name, which only lox functions have
+    fn call_value_named(&mut self, paren: &Token, callee: Value, args: Vec<Value>, named: Vec<(Token, Value)>) -> Result<Value, RuntimeError> {
+	let given = args.len() + named.len();
+	match callee {
+	    Value::Native(function) => {
+		if let Some((name, _)) = named.first() {
+		    return Err(RuntimeError::new(name, "Can't pass arguments by name to a native function."));
+		}
+		self.call(paren, function.name.to_string(), function.arity, Some(function.arity), given, |_| {
+		    function.call(&args).map_err(|error| match error {
+			NativeError::Message(message) => RuntimeError::new(paren, &message),
+			NativeError::Exit(code) => RuntimeError::exit(code),
+		    })
+		})
+	    },
+	    Value::Function(function) => {
+		function.check_named(paren, args.len(), &named)?;
+		self.call(paren, function.name(), function.min_arity(), function.max_arity(), given, |interpreter| {
+		    function.call_named(interpreter, args, named)
+		})
+	    },
+	    // LoxClass.call: jlox/LoxClass.java L34
+	    Value::Class(class) => {
+		let instance = Value::Instance(Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&class)))));
+		let init = class.find_method("init");
+		if let Some(init) = &init {
+		    init.check_named(paren, args.len(), &named)?;
+		}
+		self.call(paren, class.name.clone(), class.min_arity(), class.max_arity(), given, |interpreter| {
+		    if let Some(init) = init {
+			init.bind(instance.clone()).call_named(interpreter, args, named)?;
+		    }
+		    Ok(instance)
+		})
+	    },
+	    _ => Err(RuntimeError::new(paren, "Can only call functions and classes."))
 	}
     }
 
-}
+    // object.name: LoxInstance.get, jlox/LoxInstance.java L15
+    // an instance's fields come first, then getters, which are called, then methods.
+    // on a class, the static methods, and on a module, its declarations
+    fn get_property(&mut self, name: &Token, object: Value) -> Result<Value, RuntimeError> {
+	match &object {
+	    Value::Instance(instance) => {
+		let (field, class) = {
+		    let instance = instance.borrow();
+		    (instance.fields.get(&name.lexeme).cloned(), Rc::clone(&instance.class))
+		};
+		if let Some(value) = field {
+		    return Ok(value);
+		}
+		if let Some(getter) = class.find_getter(&name.lexeme) {
+		    let getter = getter.bind(object.clone());
+		    return self.call(name, getter.name(), 0, Some(0), 0, |interpreter| {
+			getter.call(interpreter, vec![])
+		    });
+		}
+		if let Some(method) = class.find_method(&name.lexeme) {
+		    return Ok(Value::Function(Rc::new(method.bind(object.clone()))));
+		}
+	    },
+	    Value::Class(class) => {
+		if let Some(method) = class.find_static(&name.lexeme) {
+		    return Ok(Value::Function(Rc::new(method.bind(object.clone()))));
+		}
+	    },
+	    Value::Module(module) => {
+		if let Some(value) = module.get(&name.lexeme) {
+		    return Ok(value);
+		}
+	    },
+	    _ => return Err(RuntimeError::new(name, "Only instances have properties."))
+	}
+	Err(RuntimeError::new(name, &format!("Undefined property '{}'.", name.lexeme)))
+    }
+
+    // object.name = value: LoxInstance.set, jlox/LoxInstance.java L28
+    // a setter takes the place of the field if the class has one
+    fn set_property(&mut self, name: &Token, object: Value, value: Value) -> Result<Value, RuntimeError> {
+	match &object {
+	    Value::Instance(instance) => {
+		let setter = instance.borrow().class.find_setter(&name.lexeme);
+		match setter {
+		    Some(setter) => {
+			let setter = setter.bind(object.clone());
+			self.call(name, setter.name(), 1, Some(1), 1, |interpreter| {
+			    setter.call(interpreter, vec![value.clone()])
+			})?;
+		    },
+		    None => { instance.borrow_mut().fields.insert(name.lexeme.clone(), value.clone()); }
+		}
+		Ok(value)
+	    },
+	    _ => Err(RuntimeError::new(name, "Only instances have fields."))
+	}
+    }
+
+    // object.name(args), for the interpreter's own calls into Lox code
+    fn invoke(&mut self, token: &Token, object: Value, name: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+	let name = Token { lexeme: name.to_string(), ..token.clone() };
+	let method = self.get_property(&name, object)?;
+	self.call_value(token, method, args)
+    }
+
+    // runs the module at path the first time it's imported, and gives back the same
+    // module every time after, or the same failure without running it again if it
+    // had errors. paths are relative to the importing file's directory
+    fn import(&mut self, keyword: &Token, path: &str) -> Result<Rc<LoxModule>, RuntimeError> {
+	let directory = self.importing.last().and_then(|file| file.parent()).unwrap_or_else(|| Path::new(""));
+	let file = fs::canonicalize(directory.join(path))
+	    .map_err(|_| RuntimeError::new(keyword, &format!("Can't find module '{}'.", path)))?;
+	if let Some(module) = self.modules.get(&file) {
+	    return Ok(Rc::clone(module));
+	}
+	if self.failed_modules.contains(&file) {
+	    return Err(RuntimeError::new(keyword, &format!("Module '{}' failed the first time it was imported.", path)));
+	}
+	if let Some(start) = self.importing.iter().position(|importing| importing == &file) {
+	    let cycle: Vec<String> = self.importing[start..].iter().chain(std::iter::once(&file))
+		.map(|file| file.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned()))
+		.collect();
+	    return Err(RuntimeError::new(keyword, &format!("Import cycle: {}.", cycle.join(" imports "))));
+	}
 
-impl stmt::Visitor<()> for Interpreter {
-    fn visit_block(&mut self, stmts: &Vec<Stmt>) {
-	let prev = self.environment.clone();
-	let new = Rc::new(RefCell::new(Environment::from(&self.environment)));
-	self.environment = new;
+	let source = fs::read_to_string(&file)
+	    .map_err(|_| RuntimeError::new(keyword, &format!("Can't read module '{}'.", path)))?;
+	let natives = Interpreter::natives(self.allow_io, &self.args, &self.error_class);
+	let stmts = match crate::lox::compile(source, self.max_parse_depth, self.strict, natives.names()) {
+	    Some(stmts) => stmts,
+	    None => {
+		self.failed_modules.insert(file);
+		return Err(RuntimeError::new(keyword, &format!("Module '{}' has errors.", path)));
+	    },
+	};
+
+	let environment = Rc::new(RefCell::new(Environment::from(&Rc::new(RefCell::new(natives)))));
+	let previous = std::mem::replace(&mut self.environment, Rc::clone(&environment));
+	self.importing.push(file.clone());
+	let mut result = Ok(());
 	for stmt in stmts.iter() {
-	    self.execute(stmt);
+	    // the parser doesn't let break, continue or return get this far
+	    if let Err(Unwind::Error(error)) = self.execute(stmt) {
+		result = Err(error);
+		break;
+	    }
 	}
-	self.environment = prev;
+	self.importing.pop();
+	self.environment = previous;
+	if result.is_err() {
+	    self.failed_modules.insert(file.clone());
+	}
+	result?;
+
+	let module = Rc::new(LoxModule { name: path.to_string(), environment });
+	self.modules.insert(file, Rc::clone(&module));
+	Ok(module)
     }
 
-    fn visit_print(&mut self, expr: &Expr) {
-	let val = self.evaluate(expr);
-	println!("{}", val);	
+    // a class's or trait's methods, getters, setters and statics, in that order
+    fn method_tables(methods: &[Method], closure: &Rc<RefCell<Environment>>) -> [HashMap<String, Rc<LoxFunction>>; 4] {
+	let mut tables: [HashMap<String, Rc<LoxFunction>>; 4] = Default::default();
+	for method in methods.iter() {
+	    let declaration = &method.declaration;
+	    let method_name = declaration.name.as_ref().map_or(String::new(), |name| name.lexeme.clone());
+	    let table = match method.kind {
+		MethodKind::Method => 0,
+		MethodKind::Getter => 1,
+		MethodKind::Setter => 2,
+		MethodKind::Static => 3,
+	    };
+	    let function = LoxFunction {
+		declaration: Rc::clone(declaration),
+		closure: Rc::clone(closure),
+		is_initializer: method.kind == MethodKind::Method && method_name == "init",
+	    };
+	    tables[table].insert(method_name, Rc::new(function));
+	}
+	tables
     }
 
-    fn visit_expression(&mut self, expr: &Expr) {
-	self.evaluate(expr);
+    // the name in a superclass or trait expression, which the parser makes a Variable
+    fn variable_name<'a>(expr: &'a Expr, default: &'a Token) -> &'a Token {
+	match expr {
+	    Expr::Variable { name, .. } => name,
+	    _ => default,
+	}
     }
 
-    fn visit_var(&mut self, name: &Token, initializer: &Expr) {
-	let value = self.evaluate(initializer);
-	self.environment.borrow_mut().define(&name.lexeme, value);	
+    // Interpreter.lookUpVariable: jlox/Interpreter.java L190
+    // the resolver gives every variable a depth, but code it hasn't seen would still
+    // find the name by looking up the chain
+    fn look_up_variable(&self, name: &Token, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+	match depth.get() {
+	    Some(distance) => self.environment.borrow().get_at(distance, name),
+	    None => self.environment.borrow().get(name),
+	}
     }
-}
 
-impl expr::Visitor<Value> for Interpreter {
-    fn visit_assignment(&mut self, name: &Token, value: &Expr) -> Value {
-	let val = self.evaluate(value);
-	self.environment.borrow_mut().assign(name, val.clone());
-	val
+    fn assign_variable(&self, name: &Token, depth: &Cell<Option<usize>>, value: Value) -> Result<(), RuntimeError> {
+	match depth.get() {
+	    Some(distance) => self.environment.borrow_mut().assign_at(distance, name, value),
+	    None => self.environment.borrow_mut().assign(name, value),
+	}
+    }
+
+    // visit_for_in, once the loop's depth is charged
+    fn for_in(&mut self, name: &Token, keyword: &Token, iterable: &Expr, body: &Stmt) -> Result<(), Unwind> {
+	let items: Vec<Value> = match self.evaluate(iterable)? {
+	    Value::List(items) => items.borrow().clone(),
+	    Value::Map(entries) => entries.borrow().keys().map(|key| key.to_value()).collect(),
+	    Value::Strng(s) => s.chars().map(|c| Value::from(c.to_string())).collect(),
+	    // counted out as it goes, so a long range doesn't need a list of all of it
+	    Value::Range(start, end) => {
+		let mut n = start;
+		while n < end {
+		    if !self.for_in_body(name, Value::Number(n), body)? { break; }
+		    n += 1.0;
+		}
+		return Ok(());
+	    },
+	    object @ Value::Instance(_) => {
+		let iterator = self.invoke(keyword, object, "iterator", vec![])?;
+		loop {
+		    let more = self.invoke(keyword, iterator.clone(), "hasNext", vec![])?;
+		    if !self.is_truthy(more) { break; }
+		    let item = self.invoke(keyword, iterator.clone(), "next", vec![])?;
+		    if !self.for_in_body(name, item, body)? { break; }
+		}
+		return Ok(());
+	    },
+	    _ => return Err(Unwind::Error(RuntimeError::new(keyword,
+		"Can only iterate over lists, maps, strings, ranges and instances with an iterator() method.")))
+	};
+
+	for item in items {
+	    if !self.for_in_body(name, item, body)? { break; }
+	}
+	Ok(())
     }
 
-    fn visit_literal(&self, val: &Value) -> Value {
-	val.clone()
+    // one time round a for-in loop, false when the body breaks out of the loop
+    // a new environment each time round, so closures in the body see their own item
+    fn for_in_body(&mut self, name: &Token, item: Value, body: &Stmt) -> Result<bool, Unwind> {
+	let mut environment = Environment::from(&self.environment);
+	environment.define(&name.lexeme, item);
+	match self.execute_block(std::slice::from_ref(body), Rc::new(RefCell::new(environment))) {
+	    Ok(()) | Err(Unwind::Continue) => Ok(true),
+	    Err(Unwind::Break) => Ok(false),
+	    Err(error) => Err(error),
+	}
     }
 
-    fn visit_grouping(&mut self, expression: &Expr) -> Value {
-	self.evaluate(expression)
+    // checks the arguments and budget, then makes the call with a frame on the stack
+    // takes min to max arguments, or any number from min when max is None; given is how
+    // many it got, and call makes the call with them
+    fn call<F>(&mut self, paren: &Token, name: String, min: usize, max: Option<usize>, given: usize, call: F) -> Result<Value, RuntimeError>
+	where F: FnOnce(&mut Interpreter) -> Result<Value, RuntimeError> {
+	if given < min || max.is_some_and(|max| given > max) {
+	    let expected = match max {
+		Some(max) if max == min => format!("{}", min),
+		Some(max) => format!("{} to {}", min, max),
+		None => format!("at least {}", min),
+	    };
+	    return Err(RuntimeError::new(paren,
+		&format!("Expected {} arguments but got {}.", expected, given)));
+	}
+	if let Some(max) = self.limits.max_call_depth {
+	    if self.call_depth >= max {
+		return Err(RuntimeError::limit(ErrorKind::CallDepth, Some(paren.line),
+		    &format!("Exceeded the limit of {} nested calls.", max)));
+	    }
+	}
+	if self.depth + CALL_DEPTH > self.max_depth {
+	    return Err(RuntimeError::limit(ErrorKind::StackOverflow, Some(paren.line), "Stack overflow."));
+	}
+	self.depth += CALL_DEPTH;
+	self.call_depth += 1;
+	self.frames.push(CallFrame { function: name, line: paren.line });
+	let mut result = call(self);
+	// the innermost call an error unwinds through sees the whole stack
+	if let Err(error) = &mut result {
+	    if error.trace.is_empty() { error.trace = self.frames.clone(); }
+	}
+	self.frames.pop();
+	self.call_depth -= 1;
+	self.depth -= CALL_DEPTH;
+	result
+    }
+
+    fn check_budget(&mut self) -> Result<(), RuntimeError> {
+	self.steps += 1;
+	if let Some(max) = self.limits.max_steps {
+	    if self.steps > max {
+		return Err(RuntimeError::limit(ErrorKind::StepLimit, None,
+		    &format!("Exceeded the limit of {} statements.", max)));
+	    }
+	}
+	if let Some(deadline) = self.limits.deadline {
+	    if Instant::now() >= deadline {
+		return Err(RuntimeError::limit(ErrorKind::Timeout, None, "Script timed out."));
+	    }
+	}
+	if let Some(cancel) = &self.limits.cancel {
+	    if cancel.load(Ordering::Relaxed) {
+		return Err(RuntimeError::limit(ErrorKind::Cancelled, None, "Script was cancelled."));
+	    }
+	}
+	Ok(())
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+	self.nest()?;
+	let result = expr.accept(self);
+	self.depth -= 1;
+	result
     }
 
-    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Value {
-	let lt = self.evaluate(left);
-	let rt = self.evaluate(right);
-	
+    // one level deeper into the rust stack; callers undo it with self.depth -= 1
+    fn nest(&mut self) -> Result<(), RuntimeError> {
+	if self.depth >= self.max_depth {
+	    return Err(RuntimeError::limit(ErrorKind::StackOverflow, None, "Stack overflow."));
+	}
+	self.depth += 1;
+	Ok(())
+    }
+
+    fn is_truthy(&self, val: Value) -> bool {
+	use Value::*;
+	match val {
+	    False => false,
+	    True => true,
+	    Nil => false,
+	    Number(_) => true,
+	    Strng(_) => true,
+	    Native(_) => true,
+	    Function(_) => true,
+	    Class(_) => true,
+	    Instance(_) => true,
+	    Trait(_) => true,
+	    Module(_) => true,
+	    List(_) => true,
+	    Map(_) => true,
+	    Range(..) => true,
+	}
+    }
+
+    fn is_equal(&self, a: Value, b: Value) -> bool {
+	a == b
+    }
+
+    // a whole number in 0..len, for indexing a list
+    fn list_index(&self, bracket: &Token, index: Value, len: usize) -> Result<usize, RuntimeError> {
+	match index {
+	    Value::Number(n) if n.fract() == 0.0 => {
+		if n < 0.0 || n >= len as f64 {
+		    Err(RuntimeError::new(bracket, &format!("List index {} out of range for length {}.", n, len)))
+		} else {
+		    Ok(n as usize)
+		}
+	    },
+	    _ => Err(RuntimeError::new(bracket, "List index must be an integer."))
+	}
+    }
+
+    // a binary operator, dispatched to a special method when an operand is an instance:
+    //   a + b    a.__add__(b), or else b.__radd__(a), and the same for the other arithmetic
+    //   a < b    a.__lt__(b), or else b.__gt__(a), and the same for the other comparisons
+    //   a == b   a.__eq__(b), or else b.__eq__(a), and != is the opposite
+    // without one, it's the usual operator: == is identity, and + is an error
+    fn operate(&mut self, operator: &Token, lt: Value, rt: Value) -> Result<Value, RuntimeError> {
+	use TokenType::*;
+	if !matches!(lt, Value::Instance(_)) && !matches!(rt, Value::Instance(_)) {
+	    return self.binary(operator, lt, rt);
+	}
+	let (method, reflected) = match operator.type_ {
+	    Plus => ("__add__", "__radd__"),
+	    Minus => ("__sub__", "__rsub__"),
+	    Star => ("__mul__", "__rmul__"),
+	    Slash => ("__div__", "__rdiv__"),
+	    SlashSlash => ("__floordiv__", "__rfloordiv__"),
+	    Percent => ("__mod__", "__rmod__"),
+	    StarStar => ("__pow__", "__rpow__"),
+	    Less => ("__lt__", "__gt__"),
+	    LessEqual => ("__le__", "__ge__"),
+	    Greater => ("__gt__", "__lt__"),
+	    GreaterEqual => ("__ge__", "__le__"),
+	    EqualEqual | BangEqual => ("__eq__", "__eq__"),
+	    _ => return self.binary(operator, lt, rt),
+	};
+	let result = if let Some(method) = self.special_method(&lt, method) {
+	    self.call_value(operator, method, vec![rt])?
+	} else if let Some(method) = self.special_method(&rt, reflected) {
+	    self.call_value(operator, method, vec![lt])?
+	} else {
+	    return self.binary(operator, lt, rt);
+	};
+	if operator.type_ == BangEqual {
+	    return Ok(Value::from(!self.is_truthy(result)));
+	}
+	Ok(result)
+    }
+
+    // an instance's method, bound, when its class has one called name
+    fn special_method(&self, value: &Value, name: &str) -> Option<Value> {
+	match value {
+	    Value::Instance(instance) => {
+		let method = instance.borrow().class.find_method(name)?;
+		Some(Value::Function(Rc::new(method.bind(value.clone()))))
+	    },
+	    _ => None
+	}
+    }
+
+    // how print shows a value: an instance with a __str__ method shows what it returns
+    // only print calls __str__. lists and maps show the instances in them the usual
+    // way, and so do error messages, like an uncaught exception's
+    fn stringify(&mut self, token: &Token, value: Value) -> Result<String, RuntimeError> {
+	match self.special_method(&value, "__str__") {
+	    Some(method) => match self.call_value(token, method, vec![])? {
+		Value::Strng(s) => Ok(s),
+		_ => Err(RuntimeError::new(token, "__str__ must return a string.")),
+	    },
+	    None => value.printed().ok_or_else(|| RuntimeError::new(token,
+		&format!("Can't print lists and maps nested more than {} deep.", expr::MAX_NESTING))),
+	}
+    }
+
+    // applies a binary operator to values already evaluated
+    fn binary(&self, operator: &Token, lt: Value, rt: Value) -> Result<Value, RuntimeError> {
 	use TokenType::*;
 	// note: would be less verbose to implement
 	// https://doc.rust-lang.org/std/ops/index.html
 	// for the Value enum
 	// then just apply them
-	match operator.type_ {
-	    Greater => { 
+	let val = match operator.type_ {
+	    Greater => {
 		Value::from(
-		    self.numeric(lt) > self.numeric(rt)
+		    self.numeric(operator, lt)? > self.numeric(operator, rt)?
 		)
 	    } ,
 	    GreaterEqual => {
 		Value::from(
-		    self.numeric(lt) >= self.numeric(rt)
+		    self.numeric(operator, lt)? >= self.numeric(operator, rt)?
 		)
 	    },
 	    Less => {
 		Value::from(
-		    self.numeric(lt) < self.numeric(rt)
+		    self.numeric(operator, lt)? < self.numeric(operator, rt)?
 		)
 	    },
 	    LessEqual => {
 		Value::from(
-		    self.numeric(lt) <= self.numeric(rt)
+		    self.numeric(operator, lt)? <= self.numeric(operator, rt)?
 		)
 	    },
 	    Minus => {
 		Value::from(
-		    self.numeric(lt) - self.numeric(rt)
+		    self.numeric(operator, lt)? - self.numeric(operator, rt)?
 		)
 	    },
 	    Plus => {
@@ -138,37 +717,536 @@ impl expr::Visitor<Value> for Interpreter {
 		match (lt, rt) {
 		    (Value::Strng(l), Value::Strng(r)) => Value::from(l + &r),
 		    (Value::Number(l), Value::Number(r)) => Value::from(l + r),
-		    (_, _) => panic!("operands to plus must both be numbers or both be strings"),
+		    (_, _) => return Err(RuntimeError::new(operator,
+			"Operands must be two numbers or two strings.")),
 		}
 	    },
 	    Slash => {
 		Value::from(
-		    self.numeric(lt) / self.numeric(rt)
+		    self.numeric(operator, lt)? / self.divisor(operator, rt)?
+		)
+	    },
+	    SlashSlash => {
+		Value::from(
+		    (self.numeric(operator, lt)? / self.divisor(operator, rt)?).floor()
 		)
 	    },
 	    Star => {
 		Value::from(
-		    self.numeric(lt) * self.numeric(rt)
+		    self.numeric(operator, lt)? * self.numeric(operator, rt)?
+		)
+	    },
+	    // the sign follows the left operand, like C's fmod
+	    Percent => {
+		Value::from(
+		    self.numeric(operator, lt)? % self.divisor(operator, rt)?
+		)
+	    },
+	    StarStar => {
+		Value::from(
+		    self.numeric(operator, lt)?.powf(self.numeric(operator, rt)?)
 		)
 	    },
 	    BangEqual => { Value::from(!self.is_equal(lt, rt)) },
 	    EqualEqual => { Value::from(self.is_equal(lt, rt)) },
 	    _ => Value::Nil
+	};
+	Ok(val)
+    }
+
+    // object[index], for values already evaluated
+    fn get_index(&self, bracket: &Token, object: &Value, index: Value) -> Result<Value, RuntimeError> {
+	match object {
+	    Value::List(items) => {
+		let items = items.borrow();
+		let i = self.list_index(bracket, index, items.len())?;
+		Ok(items[i].clone())
+	    },
+	    // a missing key is nil, use has() to tell it apart from a nil value
+	    Value::Map(entries) => {
+		let key = MapKey::from_value(&index).map_err(|message| RuntimeError::new(bracket, &message))?;
+		Ok(entries.borrow().get(&key).cloned().unwrap_or(Value::Nil))
+	    },
+	    _ => Err(RuntimeError::new(bracket, "Only lists and maps can be indexed."))
+	}
+    }
+
+    fn set_index(&self, bracket: &Token, object: &Value, index: Value, value: Value) -> Result<Value, RuntimeError> {
+	match object {
+	    Value::List(items) => {
+		let mut items = items.borrow_mut();
+		let i = self.list_index(bracket, index, items.len())?;
+		items[i] = value.clone();
+		Ok(value)
+	    },
+	    Value::Map(entries) => {
+		let key = MapKey::from_value(&index).map_err(|message| RuntimeError::new(bracket, &message))?;
+		entries.borrow_mut().insert(key, value.clone());
+		Ok(value)
+	    },
+	    _ => Err(RuntimeError::new(bracket, "Only lists and maps can be indexed."))
+	}
+    }
+
+    fn numeric(&self, operator: &Token, operand: Value) -> Result<f64, RuntimeError> {
+	match operand {
+	    Value::Number(n) => Ok(n),
+	    _ => Err(RuntimeError::new(operator, "Operand must be a number."))
+	}
+    }
+
+    // the right operand of /, // and %
+    fn divisor(&self, operator: &Token, operand: Value) -> Result<f64, RuntimeError> {
+	let n = self.numeric(operator, operand)?;
+	if n == 0.0 && self.strict_division {
+	    return Err(RuntimeError::new(operator, "Division by zero."));
 	}
+	Ok(n)
     }
 
-    fn visit_unary(&mut self, operator: &Token, expr: &Expr) -> Value {
-	let right = self.evaluate(expr);
+}
+
+impl stmt::Visitor<Result<(), Unwind>> for Interpreter {
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) -> Result<(), Unwind> {
+	let environment = Rc::new(RefCell::new(Environment::from(&self.environment)));
+	self.execute_block(stmts, environment)
+    }
+
+    fn visit_break(&mut self, _keyword: &Token) -> Result<(), Unwind> {
+	Err(Unwind::Break)
+    }
+
+    fn visit_continue(&mut self, _keyword: &Token) -> Result<(), Unwind> {
+	Err(Unwind::Continue)
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) -> Result<(), Unwind> {
+	let condition = self.evaluate(condition)?;
+	if self.is_truthy(condition) {
+	    self.execute(then_branch)
+	} else if let Some(else_branch) = else_branch {
+	    self.execute(else_branch)
+	} else {
+	    Ok(())
+	}
+    }
+
+    // values are compared like ==, so a list only matches itself
+    fn visit_match(&mut self, _keyword: &Token, value: &Expr, arms: &[MatchArm]) -> Result<(), Unwind> {
+	let value = self.evaluate(value)?;
+	for arm in arms.iter() {
+	    let mut environment = Environment::from(&self.environment);
+	    let matched = arm.patterns.iter().any(|pattern| match pattern {
+		Pattern::Literal(literal) => self.is_equal(value.clone(), literal.clone()),
+		Pattern::Wildcard => true,
+		Pattern::Binding(name) => {
+		    environment.define(&name.lexeme, value.clone());
+		    true
+		},
+	    });
+	    if !matched { continue; }
+
+	    let environment = Rc::new(RefCell::new(environment));
+	    if let Some(guard) = &arm.guard {
+		let passed = self.evaluate_in(guard, Rc::clone(&environment))?;
+		if !self.is_truthy(passed) { continue; }
+	    }
+	    return self.execute_block(std::slice::from_ref(arm.body.as_ref()), environment);
+	}
+	Ok(())
+    }
+
+    fn visit_import(&mut self, keyword: &Token, path: &str, imported: &Imported) -> Result<(), Unwind> {
+	let module = self.import(keyword, path)?;
+	match imported {
+	    Imported::Module(name) => {
+		self.environment.borrow_mut().declare(name, Value::Module(module), false)?;
+	    },
+	    Imported::Names(names) => {
+		for name in names.iter() {
+		    let value = module.get(&name.lexeme).ok_or_else(|| {
+			RuntimeError::new(name, &format!("Module '{}' has no '{}'.", module.name, name.lexeme))
+		    })?;
+		    self.environment.borrow_mut().declare(name, value, false)?;
+		}
+	    },
+	}
+	Ok(())
+    }
+
+    fn visit_print(&mut self, keyword: &Token, expr: &Expr) -> Result<(), Unwind> {
+	let val = self.evaluate(expr)?;
+	println!("{}", self.stringify(keyword, val)?);
+	Ok(())
+    }
+
+    fn visit_expression(&mut self, expr: &Expr) -> Result<(), Unwind> {
+	self.evaluate(expr)?;
+	Ok(())
+    }
+
+    // Interpreter.visitClassStmt: jlox/Interpreter.java L55
+    // a trait's methods are copied into the class's tables, under the class's own
+    // methods and over the superclass's. in a trait method, `super` is the superclass
+    // of the class it's copied into. copies always get an environment for `super`,
+    // even when it's left empty, since the resolver can't know which classes have one
+    fn visit_class(&mut self, name: &Token, superclass: &Option<Box<Expr>>, traits: &[Expr], methods: &[Method]) -> Result<(), Unwind> {
+	let superclass = match superclass {
+	    Some(expr) => match self.evaluate(expr)? {
+		Value::Class(class) => Some(class),
+		_ => return Err(Unwind::Error(RuntimeError::new(Interpreter::variable_name(expr, name),
+		    "Superclass must be a class."))),
+	    },
+	    None => None,
+	};
+
+	// methods close over an environment with `super` in it, when there is one
+	let closure = match &superclass {
+	    Some(superclass) => {
+		let mut environment = Environment::from(&self.environment);
+		environment.define("super", Value::Class(Rc::clone(superclass)));
+		Rc::new(RefCell::new(environment))
+	    },
+	    None => Rc::clone(&self.environment),
+	};
+
+	let mut tables = Interpreter::method_tables(methods, &closure);
+	let own: Vec<Vec<String>> = tables.iter().map(|table| table.keys().cloned().collect()).collect();
+	// which trait each copied method came from, to report two traits with the same one
+	let mut mixed_in: [HashMap<String, String>; 4] = Default::default();
+	for expr in traits.iter() {
+	    let token = Interpreter::variable_name(expr, name);
+	    let mixin = match self.evaluate(expr)? {
+		Value::Trait(mixin) => mixin,
+		_ => return Err(Unwind::Error(RuntimeError::new(token, "Can only mix in traits."))),
+	    };
+	    let trait_tables = [&mixin.methods, &mixin.getters, &mixin.setters, &mixin.statics];
+	    for (i, trait_table) in trait_tables.iter().enumerate() {
+		for (method_name, function) in trait_table.iter() {
+		    if own[i].contains(method_name) { continue; }
+		    if let Some(other) = mixed_in[i].get(method_name) {
+			return Err(Unwind::Error(RuntimeError::new(token, &format!(
+			    "Traits '{}' and '{}' both define '{}'.", other, mixin.name, method_name))));
+		    }
+		    mixed_in[i].insert(method_name.clone(), mixin.name.clone());
+		    let mut environment = Environment::from(&function.closure);
+		    if let Some(superclass) = &superclass {
+			environment.define("super", Value::Class(Rc::clone(superclass)));
+		    }
+		    let function = LoxFunction {
+			declaration: Rc::clone(&function.declaration),
+			closure: Rc::new(RefCell::new(environment)),
+			is_initializer: function.is_initializer,
+		    };
+		    tables[i].insert(method_name.clone(), Rc::new(function));
+		}
+	    }
+	}
+	let [methods, getters, setters, statics] = tables;
+
+	let class = LoxClass { name: name.lexeme.clone(), superclass, methods, getters, setters, statics };
+	self.environment.borrow_mut().declare(name, Value::Class(Rc::new(class)), false)?;
+	Ok(())
+    }
+
+    fn visit_trait(&mut self, name: &Token, methods: &[Method]) -> Result<(), Unwind> {
+	let closure = Rc::clone(&self.environment);
+	let [methods, getters, setters, statics] = Interpreter::method_tables(methods, &closure);
+	let mixin = LoxTrait { name: name.lexeme.clone(), methods, getters, setters, statics };
+	self.environment.borrow_mut().declare(name, Value::Trait(Rc::new(mixin)), false)?;
+	Ok(())
+    }
+
+    fn visit_function(&mut self, declaration: &Rc<FunctionDecl>) -> Result<(), Unwind> {
+	let function = self.function(declaration);
+	if let Some(name) = &declaration.name {
+	    self.environment.borrow_mut().declare(name, function, false)?;
+	}
+	Ok(())
+    }
+
+    fn visit_return(&mut self, _keyword: &Token, value: &Option<Box<Expr>>) -> Result<(), Unwind> {
+	let value = match value {
+	    Some(value) => self.evaluate(value)?,
+	    None => Value::Nil,
+	};
+	Err(Unwind::Return(value))
+    }
+
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> Result<(), Unwind> {
+	let value = self.evaluate(value)?;
+	Err(Unwind::Error(RuntimeError::thrown(keyword, value)))
+    }
+
+    fn visit_try(&mut self, body: &[Stmt], catch: &Option<(Token, Vec<Stmt>)>, finally: &Option<Vec<Stmt>>) -> Result<(), Unwind> {
+	let environment = Rc::new(RefCell::new(Environment::from(&self.environment)));
+	let mut result = self.execute_block(body, environment);
+
+	if let (Err(Unwind::Error(error)), Some((name, handler))) = (&result, catch) {
+	    if error.is_catchable() {
+		let mut environment = Environment::from(&self.environment);
+		environment.define(&name.lexeme, self.caught(error));
+		result = self.execute_block(handler, Rc::new(RefCell::new(environment)));
+	    }
+	}
+
+	// like java: if finally itself returns, breaks or throws, that wins,
+	// otherwise whatever the try or catch was doing carries on
+	if let Some(finally) = finally {
+	    let environment = Rc::new(RefCell::new(Environment::from(&self.environment)));
+	    self.execute_block(finally, environment)?;
+	}
+	result
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: &Expr, constant: bool) -> Result<(), Unwind> {
+	let value = self.evaluate(initializer)?;
+	self.environment.borrow_mut().declare(name, value, constant)?;
+	Ok(())
+    }
+
+    // lists and maps are copied before the loop starts, so changing them in the body
+    // doesn't change what's iterated over.
+    // an instance is iterated with its iterator() method, which gives back an object
+    // with hasNext() and next(), called as the loop goes
+    fn visit_for_in(&mut self, name: &Token, keyword: &Token, iterable: &Expr, body: &Stmt) -> Result<(), Unwind> {
+	if self.depth + FOR_IN_DEPTH > self.max_depth {
+	    return Err(Unwind::Error(RuntimeError::limit(ErrorKind::StackOverflow, Some(keyword.line), "Stack overflow.")));
+	}
+	self.depth += FOR_IN_DEPTH;
+	let result = self.for_in(name, keyword, iterable, body);
+	self.depth -= FOR_IN_DEPTH;
+	result
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Box<Expr>>) -> Result<(), Unwind> {
+	loop {
+	    let value = self.evaluate(condition)?;
+	    if !self.is_truthy(value) { break; }
+	    match self.execute(body) {
+		Ok(()) | Err(Unwind::Continue) => {},
+		Err(Unwind::Break) => break,
+		Err(error) => return Err(error),
+	    }
+	    if let Some(increment) = increment {
+		self.evaluate(increment)?;
+	    }
+	}
+	Ok(())
+    }
+}
+
+impl expr::Visitor<Result<Value, RuntimeError>> for Interpreter {
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+	let val = self.evaluate(value)?;
+	self.assign_variable(name, depth, val.clone())?;
+	Ok(val)
+    }
+
+    fn visit_lambda(&mut self, declaration: &Rc<FunctionDecl>) -> Result<Value, RuntimeError> {
+	Ok(self.function(declaration))
+    }
+
+    fn visit_literal(&self, val: &Value) -> Result<Value, RuntimeError> {
+	Ok(val.clone())
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) -> Result<Value, RuntimeError> {
+	self.evaluate(expression)
+    }
+
+    fn visit_comma(&mut self, left: &Expr, right: &Expr) -> Result<Value, RuntimeError> {
+	self.evaluate(left)?;
+	self.evaluate(right)
+    }
+
+    // only the branch that's taken is evaluated
+    fn visit_ternary(&mut self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> Result<Value, RuntimeError> {
+	let condition = self.evaluate(condition)?;
+	if self.is_truthy(condition) {
+	    self.evaluate(then_branch)
+	} else {
+	    self.evaluate(else_branch)
+	}
+    }
+
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Value, RuntimeError> {
+	let lt = self.evaluate(left)?;
+	let rt = self.evaluate(right)?;
+	self.operate(operator, lt, rt)
+    }
+
+    fn visit_compound_assign(&mut self, target: &Expr, operator: &Token, value: &Expr, postfix: bool) -> Result<Value, RuntimeError> {
+	// the old value is read before the right hand side is evaluated, like x = x + value
+	match target {
+	    Expr::Variable { name, depth } => {
+		let old = self.look_up_variable(name, depth)?;
+		let value = self.evaluate(value)?;
+		let new = self.operate(operator, old.clone(), value)?;
+		self.assign_variable(name, depth, new.clone())?;
+		Ok(if postfix { old } else { new })
+	    },
+	    Expr::Index { object, bracket, index } => {
+		let object = self.evaluate(object)?;
+		let index = self.evaluate(index)?;
+		let old = self.get_index(bracket, &object, index.clone())?;
+		let value = self.evaluate(value)?;
+		let new = self.operate(operator, old.clone(), value)?;
+		self.set_index(bracket, &object, index, new.clone())?;
+		Ok(if postfix { old } else { new })
+	    },
+	    Expr::Get { object, name } => {
+		let object = self.evaluate(object)?;
+		let old = self.get_property(name, object.clone())?;
+		let value = self.evaluate(value)?;
+		let new = self.operate(operator, old.clone(), value)?;
+		self.set_property(name, object, new.clone())?;
+		Ok(if postfix { old } else { new })
+	    },
+	    // the parser only makes these for variables, indexes and properties
+	    _ => Err(RuntimeError::new(operator, "Invalid assignment target."))
+	}
+    }
+
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr], named: &[(Token, Expr)]) -> Result<Value, RuntimeError> {
+	let callee = self.evaluate(callee)?;
+
+	let mut args = vec![];
+	for argument in arguments.iter() {
+	    args.push(self.evaluate(argument)?);
+	}
+	let mut named_args = vec![];
+	for (name, argument) in named.iter() {
+	    named_args.push((name.clone(), self.evaluate(argument)?));
+	}
+
+	self.call_value_named(paren, callee, args, named_args)
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Result<Value, RuntimeError> {
+	let object = self.evaluate(object)?;
+	self.get_property(name, object)
+    }
+
+    fn visit_set(&mut self, object: &Expr, name: &Token, value: &Expr) -> Result<Value, RuntimeError> {
+	let object = self.evaluate(object)?;
+	let value = self.evaluate(value)?;
+	self.set_property(name, object, value)
+    }
+
+    fn visit_this(&mut self, keyword: &Token, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+	self.look_up_variable(keyword, depth)
+    }
+
+    // Interpreter.visitSuperExpr: jlox/Interpreter.java L231
+    // `this` is in the environment just inside super's. in a static method it's the
+    // class, so super finds the superclass's static methods
+    fn visit_super(&mut self, keyword: &Token, method: &Token, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+	let distance = depth.get().unwrap_or(1);
+	let superclass = self.environment.borrow().get_here_at(distance, "super").ok_or_else(|| {
+	    RuntimeError::new(keyword, "Can't use 'super' in a class with no superclass.")
+	})?;
+	let this = self.environment.borrow().get_here_at(distance - 1, "this").unwrap_or(Value::Nil);
+	let found = match (&superclass, &this) {
+	    (Value::Class(superclass), Value::Class(_)) => superclass.find_static(&method.lexeme),
+	    (Value::Class(superclass), _) => superclass.find_method(&method.lexeme),
+	    _ => None,
+	};
+	match found {
+	    Some(found) => Ok(Value::Function(Rc::new(found.bind(this)))),
+	    None => Err(RuntimeError::new(method, &format!("Undefined property '{}'.", method.lexeme))),
+	}
+    }
+
+    fn visit_list(&mut self, elements: &[Expr]) -> Result<Value, RuntimeError> {
+	let mut items = vec![];
+	for element in elements.iter() {
+	    items.push(self.evaluate(element)?);
+	}
+	Ok(Value::from(items))
+    }
+
+    fn visit_map(&mut self, brace: &Token, entries: &[(Expr, Expr)]) -> Result<Value, RuntimeError> {
+	let mut map = BTreeMap::new();
+	for (key, value) in entries.iter() {
+	    let key = self.evaluate(key)?;
+	    let key = MapKey::from_value(&key).map_err(|message| RuntimeError::new(brace, &message))?;
+	    let value = self.evaluate(value)?;
+	    map.insert(key, value);
+	}
+	Ok(Value::from(map))
+    }
+
+    fn visit_index(&mut self, object: &Expr, bracket: &Token, index: &Expr) -> Result<Value, RuntimeError> {
+	let object = self.evaluate(object)?;
+	let index = self.evaluate(index)?;
+	self.get_index(bracket, &object, index)
+    }
+
+    fn visit_set_index(&mut self, object: &Expr, bracket: &Token, index: &Expr, value: &Expr) -> Result<Value, RuntimeError> {
+	let object = self.evaluate(object)?;
+	let index = self.evaluate(index)?;
+	let value = self.evaluate(value)?;
+	self.set_index(bracket, &object, index, value)
+    }
+
+    fn visit_unary(&mut self, operator: &Token, expr: &Expr) -> Result<Value, RuntimeError> {
+	let right = self.evaluate(expr)?;
 	match operator.type_ {
 	    TokenType::Minus => {
-		Value::from(-self.numeric(right))
+		Ok(Value::from(-self.numeric(operator, right)?))
 	    },
-	    TokenType::Bang => Value::from(!self.is_truthy(right)),
+	    TokenType::Bang => Ok(Value::from(!self.is_truthy(right))),
 	    _ => panic!("unary operator should only be minus or bang")
 	}
     }
 
-    fn visit_variable(&self, name: &Token) -> Value {
-	self.environment.borrow().get(name)
+    fn visit_variable(&mut self, name: &Token, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+	self.look_up_variable(name, depth)
+    }
+}
+
+// cancelling needs another thread holding the flag, which a script can't do,
+// so this one is tested here instead of under tests/lox
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox;
+    use crate::parser;
+
+    #[test]
+    fn cancel_stops_the_script() {
+	let mut interpreter = Interpreter::new();
+	let cancel = Arc::new(AtomicBool::new(false));
+	interpreter.set_limits(Limits { cancel: Some(Arc::clone(&cancel)), ..Limits::default() });
+	let stmts = lox::compile("while (true) {}".to_string(), parser::DEFAULT_MAX_DEPTH, false, vec![]).unwrap();
+
+	let canceller = std::thread::spawn(move || {
+	    std::thread::sleep(std::time::Duration::from_millis(20));
+	    cancel.store(true, Ordering::Relaxed);
+	});
+	let error = interpreter.interpret(stmts).unwrap_err();
+	canceller.join().unwrap();
+	assert_eq!(error.kind, ErrorKind::Cancelled);
+	assert_eq!(error.message, "Script was cancelled.");
+    }
+
+    // an embedder gets exit() back as an error, rather than its process ending
+    #[test]
+    fn exit_is_returned_to_the_embedder() {
+	let mut interpreter = Interpreter::new();
+	let stmts = lox::compile("exit(3);".to_string(), parser::DEFAULT_MAX_DEPTH, false, vec![]).unwrap();
+	let error = interpreter.interpret(stmts).unwrap_err();
+	assert_eq!(error.kind, ErrorKind::Exit(3));
+    }
+
+    // the resolver only sees one line of the prompt at a time
+    #[test]
+    fn constant_is_not_redeclared_on_a_later_line() {
+	let mut interpreter = Interpreter::new();
+	let compile = |source: &str| lox::compile(source.to_string(), parser::DEFAULT_MAX_DEPTH, false, vec![]).unwrap();
+	interpreter.interpret(compile("const x = 1;")).unwrap();
+	let error = interpreter.interpret(compile("var x = 2;")).unwrap_err();
+	assert_eq!(error.message, "Cannot assign to constant 'x'.");
     }
 }
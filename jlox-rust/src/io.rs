@@ -0,0 +1,82 @@
+// natives that reach outside the interpreter: stdin, files, environment variables
+// and exiting, defined as globals
+// an embedder can switch these off, in which case calling one is a runtime error
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::io::{self, BufRead, Write};
+use crate::expr::Value;
+use crate::lox_callable::NativeError;
+use crate::environment::Environment;
+use crate::strings::string;
+
+pub fn define_natives(globals: &mut Environment, allowed: bool) {
+    if !allowed {
+        // still defined, so scripts get a clearer error than an undefined variable
+        globals.define_native("readLine", 0, disabled);
+        globals.define_native("readFile", 1, disabled);
+        globals.define_native("writeFile", 2, disabled);
+        globals.define_native("appendFile", 2, disabled);
+        globals.define_native("fileExists", 1, disabled);
+        globals.define_native("getenv", 1, disabled);
+        globals.define_native("exit", 1, disabled);
+        return;
+    }
+
+    // one line from stdin without its newline, or nil at the end of input
+    globals.define_native("readLine", 0, |_| {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) => Ok(Value::Nil),
+            Ok(_) => {
+                let trimmed = line.trim_end_matches(&['\n', '\r'][..]);
+                Ok(Value::from(trimmed.to_string()))
+            },
+            Err(e) => Err(format!("Couldn't read from stdin: {}.", e).into())
+        }
+    });
+    globals.define_native("readFile", 1, |args| {
+        let path = string(args, 0, "readFile")?;
+        fs::read_to_string(path)
+            .map(Value::from)
+            .map_err(|e| format!("Couldn't read '{}': {}.", path, e).into())
+    });
+    globals.define_native("writeFile", 2, |args| {
+        let path = string(args, 0, "writeFile")?;
+        let text = string(args, 1, "writeFile")?;
+        fs::write(path, text)
+            .map(|_| Value::Nil)
+            .map_err(|e| format!("Couldn't write '{}': {}.", path, e).into())
+    });
+    globals.define_native("appendFile", 2, |args| {
+        let path = string(args, 0, "appendFile")?;
+        let text = string(args, 1, "appendFile")?;
+        fs::OpenOptions::new().append(true).create(true).open(path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map(|_| Value::Nil)
+            .map_err(|e| format!("Couldn't append to '{}': {}.", path, e).into())
+    });
+    globals.define_native("fileExists", 1, |args| {
+        Ok(Value::from(Path::new(string(args, 0, "fileExists")?).exists()))
+    });
+
+    // nil when the variable isn't set
+    globals.define_native("getenv", 1, |args| {
+        match env::var(string(args, 0, "getenv")?) {
+            Ok(value) => Ok(Value::from(value)),
+            Err(_) => Ok(Value::Nil)
+        }
+    });
+    // ends the script, with the exit code for the process running it. it unwinds
+    // like an error that can't be caught, so the finally blocks it's in still run
+    globals.define_native("exit", 1, |args| {
+        match args[0] {
+            Value::Number(n) if n.fract() == 0.0 => Err(NativeError::Exit(n as i32)),
+            _ => Err("Argument to exit must be an integer.".into())
+        }
+    });
+}
+
+fn disabled(_: &[Value]) -> Result<Value, NativeError> {
+    Err("Access to files, stdin and the environment is disabled.".into())
+}
@@ -0,0 +1,212 @@
+// serde support for Value, and the json natives built on it
+// only compiled with the "serde" cargo feature
+// json objects parse as instances of a class named Object, so their keys are
+// read as properties; instances of any class serialize as objects of their fields
+use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use std::collections::{HashMap, HashSet};
+use serde::de::{self, Visitor, SeqAccess, MapAccess};
+use serde::ser::{self, SerializeSeq, SerializeMap};
+use crate::expr::{Value, MAX_NESTING};
+use crate::lox_callable::NativeError;
+use crate::environment::Environment;
+use crate::lox_class::LoxClass;
+use crate::lox_instance::LoxInstance;
+
+thread_local! {
+    // one class for every parsed object, so they're all `Object instance`s of the same class
+    // it has no methods, so instances of it are only their fields
+    static OBJECT_CLASS: Rc<LoxClass> = Rc::new(LoxClass {
+        name: "Object".to_string(),
+        superclass: None,
+        methods: HashMap::new(),
+        getters: HashMap::new(),
+        setters: HashMap::new(),
+        statics: HashMap::new(),
+    });
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Serializing { value: self, seen: &RefCell::new(vec![]) }.serialize(serializer)
+    }
+}
+
+// carries the lists, maps and instances we're in the middle of down into nested
+// values, so one that contains itself is an error instead of a stack overflow
+struct Serializing<'a> {
+    value: &'a Value,
+    seen: &'a RefCell<Vec<*const ()>>,
+}
+
+impl<'a> Serializing<'a> {
+    // before writing what's in a list, map or instance
+    fn enter<E: ser::Error>(&self, ptr: *const (), what: &str) -> Result<(), E> {
+        let mut seen = self.seen.borrow_mut();
+        if seen.contains(&ptr) {
+            return Err(E::custom(format!("Can't serialize {} that contains itself.", what)));
+        }
+        if seen.len() >= MAX_NESTING {
+            return Err(E::custom(format!("Can't serialize values nested more than {} deep.", MAX_NESTING)));
+        }
+        seen.push(ptr);
+        Ok(())
+    }
+}
+
+impl<'a> Serialize for Serializing<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Nil => serializer.serialize_unit(),
+            Value::True => serializer.serialize_bool(true),
+            Value::False => serializer.serialize_bool(false),
+            // json has no NaN or infinity, and serde_json would quietly write null for them
+            Value::Number(n) if !n.is_finite() => {
+                Err(ser::Error::custom(format!("Can't serialize the number {}.", n)))
+            },
+            // keep whole numbers whole, so 3 round trips as 3 and not 3.0
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 9007199254740992.0 => {
+                serializer.serialize_i64(*n as i64)
+            },
+            Value::Number(n) => serializer.serialize_f64(*n),
+            Value::Strng(s) => serializer.serialize_str(s),
+            Value::Native(_) | Value::Function(_) => Err(ser::Error::custom("Can't serialize a function.")),
+            Value::Class(_) => Err(ser::Error::custom("Can't serialize a class.")),
+            // fields only: getters and methods aren't data. sorted, so the output is stable
+            Value::Instance(instance) => {
+                let ptr = Rc::as_ptr(instance) as *const ();
+                self.enter(ptr, "an instance")?;
+                let instance = instance.borrow();
+                let mut fields: Vec<_> = instance.fields.iter().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, value) in fields {
+                    map.serialize_entry(name, &Serializing { value, seen: self.seen })?;
+                }
+                self.seen.borrow_mut().pop();
+                map.end()
+            },
+            Value::Trait(_) => Err(ser::Error::custom("Can't serialize a trait.")),
+            Value::Module(_) => Err(ser::Error::custom("Can't serialize a module.")),
+            Value::List(items) => {
+                let ptr = Rc::as_ptr(items) as *const ();
+                self.enter(ptr, "a list")?;
+                let items = items.borrow();
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items.iter() {
+                    seq.serialize_element(&Serializing { value: item, seen: self.seen })?;
+                }
+                self.seen.borrow_mut().pop();
+                seq.end()
+            },
+            // json keys are always strings, so number keys are written as strings,
+            // and a map with both 1 and "1" has no json form
+            Value::Map(entries) => {
+                let ptr = Rc::as_ptr(entries) as *const ();
+                self.enter(ptr, "a map")?;
+                let entries = entries.borrow();
+                let mut written = HashSet::new();
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries.iter() {
+                    let key = format!("{}", key.to_value());
+                    if !written.insert(key.clone()) {
+                        return Err(ser::Error::custom(format!("Can't serialize a map with two keys written as \"{}\".", key)));
+                    }
+                    map.serialize_entry(&key, &Serializing { value, seen: self.seen })?;
+                }
+                self.seen.borrow_mut().pop();
+                map.end()
+            },
+            Value::Range(..) => Err(ser::Error::custom("Can't serialize a range.")),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any json value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::from(b))
+    }
+
+    // lox only has doubles
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<Value, E> {
+        Ok(Value::Number(n))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::Strng(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Value, E> {
+        Ok(Value::Strng(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::from(items))
+    }
+
+    // a repeated key keeps its last value, like JSON.parse
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut fields = HashMap::new();
+        while let Some((key, value)) = access.next_entry::<String, Value>()? {
+            fields.insert(key, value);
+        }
+        let class = OBJECT_CLASS.with(Rc::clone);
+        Ok(Value::Instance(Rc::new(RefCell::new(LoxInstance { class, fields }))))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+pub fn define_natives(globals: &mut Environment) {
+    globals.define_native("json_parse", 1, json_parse);
+    globals.define_native("json_stringify", 1, json_stringify);
+}
+
+fn json_parse(arguments: &[Value]) -> Result<Value, NativeError> {
+    match &arguments[0] {
+        Value::Strng(s) => {
+            serde_json::from_str(s).map_err(|e| format!("Invalid JSON: {}.", e).into())
+        },
+        _ => Err("Argument to json_parse must be a string.".into())
+    }
+}
+
+fn json_stringify(arguments: &[Value]) -> Result<Value, NativeError> {
+    serde_json::to_string(&arguments[0])
+        .map(Value::Strng)
+        .map_err(|e| e.to_string().into())
+}
@@ -0,0 +1,68 @@
+// list natives, defined as globals
+// lists are shared, so these change the list in place
+use std::rc::Rc;
+use crate::expr::{Value, Contents};
+use crate::environment::Environment;
+
+pub fn define_natives(globals: &mut Environment) {
+    globals.define_native("push", 2, |args| {
+        list(args, 0, "push")?.borrow_mut().push(args[1].clone());
+        Ok(Value::Nil)
+    });
+    globals.define_native("pop", 1, |args| {
+        match list(args, 0, "pop")?.borrow_mut().pop() {
+            Some(value) => Ok(value),
+            None => Err("Can't pop from an empty list.".into())
+        }
+    });
+    // insert(list, index, value), where index can be the length to add at the end
+    globals.define_native("insert", 3, |args| {
+        let items = list(args, 0, "insert")?;
+        let i = index(args, 1, "insert", items.borrow().len() + 1)?;
+        items.borrow_mut().insert(i, args[2].clone());
+        Ok(Value::Nil)
+    });
+    // remove(list, index) gives back what it removed
+    globals.define_native("remove", 2, |args| {
+        let items = list(args, 0, "remove")?;
+        let i = index(args, 1, "remove", items.borrow().len())?;
+        let removed = items.borrow_mut().remove(i);
+        Ok(removed)
+    });
+    // range(start, end): the whole numbers from start up to but not including end
+    globals.define_native("range", 2, |args| {
+        let (start, end) = match (&args[0], &args[1]) {
+            (Value::Number(start), Value::Number(end)) if start.fract() == 0.0 && end.fract() == 0.0 => (*start, *end),
+            _ => return Err("Arguments to range must be integers.".into())
+        };
+        if start.abs() > MAX_RANGE || end.abs() > MAX_RANGE {
+            return Err(format!("Arguments to range must be between -{} and {}.", MAX_RANGE, MAX_RANGE).into());
+        }
+        Ok(Value::Range(start, end))
+    });
+}
+
+// past 2^53, adding 1 to a number can leave it the same, so counting up a range
+// would never reach the end
+const MAX_RANGE: f64 = 9_007_199_254_740_992.0;
+
+fn list<'a>(args: &'a [Value], position: usize, name: &str) -> Result<&'a Rc<Contents<Vec<Value>>>, String> {
+    match &args[position] {
+        Value::List(items) => Ok(items),
+        _ => Err(format!("Argument {} to {} must be a list.", position + 1, name))
+    }
+}
+
+// a whole number in 0..len
+fn index(args: &[Value], position: usize, name: &str, len: usize) -> Result<usize, String> {
+    match args[position] {
+        Value::Number(n) if n.fract() == 0.0 => {
+            if n < 0.0 || n >= len as f64 {
+                Err(format!("Index {} out of range in {}.", n, name))
+            } else {
+                Ok(n as usize)
+            }
+        },
+        _ => Err(format!("Argument {} to {} must be an integer.", position + 1, name))
+    }
+}
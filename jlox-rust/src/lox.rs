@@ -1,22 +1,63 @@
 use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::process;
 use crate::scanner::*;
-use crate::parser::*;
+use crate::parser::{self, Parser};
+use crate::resolver::Resolver;
 // use crate::ast_printer::*;
-use crate::interpreter::*;
+use crate::interpreter::{self, Interpreter, Limits};
+use crate::token::Token;
+use crate::stmt::Stmt;
+use crate::token_type::TokenType;
+use crate::runtime_error::{RuntimeError, ErrorKind};
 
 pub struct Lox {
-    interpreter: Interpreter
+    interpreter: Interpreter,
+    had_error: bool,
+    had_runtime_error: bool,
+    // set when the script calls exit(code)
+    exit_code: Option<i32>,
 }
 
 impl Lox {
     pub fn new() -> Lox {
         Lox {
-            interpreter: Interpreter::new()
+            interpreter: Interpreter::new(),
+            had_error: false,
+            had_runtime_error: false,
+            exit_code: None,
         }
     }
 
+    // the defaults fit the main thread's stack; a script running on a thread
+    // with `factor` times the stack can nest `factor` times deeper
+    pub fn scale_max_depth(&mut self, factor: usize) {
+        self.interpreter.max_parse_depth = parser::DEFAULT_MAX_DEPTH * factor;
+        self.interpreter.max_depth = interpreter::DEFAULT_MAX_DEPTH * factor;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.interpreter.set_limits(limits);
+    }
+
+    pub fn allow_io(&mut self, allowed: bool) {
+        self.interpreter.allow_io(allowed);
+    }
+
+    pub fn strict_division(&mut self, strict: bool) {
+        self.interpreter.strict_division(strict);
+    }
+
+    // resolve every script in strict mode, not just the ones that ask for it
+    pub fn strict(&mut self, strict: bool) {
+        self.interpreter.strict = strict;
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.interpreter.set_args(args);
+    }
+
     // Lox.runPrompt: jlox/Lox.java L30
     pub fn run_prompt(&mut self) {
         let stdin = io::stdin();
@@ -24,6 +65,8 @@ impl Lox {
         io::stdout().flush().unwrap();
         for line in stdin.lock().lines() {
             self.run(line.unwrap());
+            if let Some(code) = self.exit_code { process::exit(code); }
+            self.had_error = false;
             print!("> ");
             io::stdout().flush().unwrap();
         }
@@ -33,26 +76,123 @@ impl Lox {
     pub fn run_file(&mut self, path: &str) {
         let contents = fs::read_to_string(path)
             .expect(&format!("an error while reading {}", path));
+        self.interpreter.set_script(path);
         self.run(contents);
+        if let Some(code) = self.exit_code {
+            io::stdout().flush().ok();
+            process::exit(code);
+        }
+        if self.had_error { process::exit(65); }
+        if self.had_runtime_error { process::exit(70); }
     }
 
     // Lox.run: jlox/Lox.java L42
     fn run(&mut self, source: String) {
+        let interpreter = &self.interpreter;
+        let stmts = match compile(source, interpreter.max_parse_depth, interpreter.strict, interpreter.global_names()) {
+            Some(stmts) => stmts,
+            None => {
+                self.had_error = true;
+                return;
+            }
+        };
+
+        // println!("{}", (AstPrinter {}).print(expression));
+        match self.interpreter.interpret(stmts) {
+            Ok(()) => {},
+            Err(RuntimeError { kind: ErrorKind::Exit(code), .. }) => self.exit_code = Some(code),
+            Err(error) => {
+                runtime_error(&error);
+                self.had_runtime_error = true;
+            },
+        }
+    }
+}
+
+    // scans, parses and resolves a script or module, reporting what's wrong with it
+    // None when something was; globals are the names a strict script can use undeclared
+    pub fn compile(source: String, max_depth: usize, strict: bool, globals: Vec<String>) -> Option<Vec<Stmt>> {
         let mut scanner = Scanner::new(source);
         let tokens = scanner.scan_tokens();
         let mut parser = Parser::new(tokens);
+        parser.max_depth = max_depth;
         let stmts = parser.parse();
+        if parser.had_error { return None; }
 
-        // println!("{}", (AstPrinter {}).print(expression));
-        self.interpreter.interpret(stmts);
+        let mut resolver = Resolver::new();
+        if strict || Resolver::has_strict_pragma(&stmts) {
+            resolver.set_strict(globals);
+        }
+        resolver.resolve_script(&stmts);
+        if resolver.had_error { return None; }
+        Some(stmts)
     }
-}
 
     // Lox.error: jlox/Lox.java L51
     pub fn error(line: usize, message: String) {
         report(line, "".to_string(), message);
     }
 
+    // Lox.error(Token, String): jlox/Lox.java L77
+    pub fn token_error(token: &Token, message: &str) {
+        match token.type_ {
+            TokenType::Eof => report(token.line, "at end".to_string(), message.to_string()),
+            _ => report(token.line, format!("at '{}'", token.lexeme), message.to_string()),
+        }
+    }
+
+    // Lox.runtimeError: jlox/Lox.java L65
+    // prints the lox call stack innermost first, like clox's runtimeError
+    pub fn runtime_error(error: &RuntimeError) {
+        eprintln!("{}", error.message);
+        // each frame's own line is where its callee was called from
+        let mut frames = vec![];
+        let mut line = error.line;
+        for frame in error.trace.iter().rev() {
+            if let Some(line) = line {
+                frames.push(format!("[line {}] in {}()", line, frame.function));
+            }
+            line = Some(frame.line);
+        }
+        if let Some(line) = line {
+            frames.push(format!("[line {}] in script", line));
+        }
+        for frame in shorten_trace(frames) {
+            eprintln!("{}", frame);
+        }
+    }
+
+    // something that's probably a mistake, but doesn't stop the script running
+    pub fn warning(token: &Token, message: &str) {
+        eprintln!("[line {} ] Warning at '{}': {}", token.line, token.lexeme, message);
+    }
+
+    // a stack overflow is a thousand frames of the same few lines, so a run of the
+    // same frame is printed once, and a long trace only at its ends, like python does
+    // how many lines are kept at each end of a long trace
+    const TRACE_ENDS: usize = 10;
+
+    fn shorten_trace(frames: Vec<String>) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        let mut i = 0;
+        while i < frames.len() {
+            let repeats = frames[i..].iter().take_while(|frame| **frame == frames[i]).count();
+            lines.push(frames[i].clone());
+            if repeats > 1 {
+                lines.push(format!("... {} more of the same frame", repeats - 1));
+            }
+            i += repeats;
+        }
+        if lines.len() > 2 * TRACE_ENDS {
+            let omitted = lines.len() - 2 * TRACE_ENDS;
+            let outermost = lines.split_off(lines.len() - TRACE_ENDS);
+            lines.truncate(TRACE_ENDS);
+            lines.push(format!("... {} more lines", omitted));
+            lines.extend(outermost);
+        }
+        lines
+    }
+
     // Lox.report jlox/Lox.java L51
     fn report(line: usize, location: String, message: String) {
         eprintln!("[line {} ] Error {}: {}", line, location, message);    
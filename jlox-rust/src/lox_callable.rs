@@ -0,0 +1,53 @@
+// maps to jlox-java file jlox/LoxCallable.java
+// Java natives are anonymous classes implementing the LoxCallable interface.
+// Here a native is a plain fn pointer, with the name and arity alongside,
+// so Value can keep deriving Clone, Debug and PartialEq
+use std::fmt;
+use crate::expr::Value;
+
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Result<Value, NativeError>,
+}
+
+// why a native didn't give back a value
+#[derive(Debug)]
+pub enum NativeError {
+    // the message for a runtime error at the call site
+    Message(String),
+    // exit(code), which ends the script once the finally blocks it's in have run
+    Exit(i32),
+}
+
+impl From<String> for NativeError {
+    fn from(message: String) -> NativeError {
+        NativeError::Message(message)
+    }
+}
+
+impl From<&str> for NativeError {
+    fn from(message: &str) -> NativeError {
+        NativeError::Message(message.to_string())
+    }
+}
+
+impl NativeFunction {
+    pub fn call(&self, arguments: &[Value]) -> Result<Value, NativeError> {
+        (self.function)(arguments)
+    }
+}
+
+// natives are only ever defined once, so the name is enough to tell them apart
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &NativeFunction) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
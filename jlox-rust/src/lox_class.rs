@@ -0,0 +1,62 @@
+// maps to jlox-java file jlox/LoxClass.java
+// besides methods, a class has tables for getters, setters and static methods.
+// static methods are called on the class itself, with `this` as the class,
+// which covers what a metaclass would without making the class an instance
+use std::fmt;
+use std::rc::Rc;
+use std::collections::HashMap;
+use crate::lox_function::LoxFunction;
+
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, Rc<LoxFunction>>,
+    // `name { ... }`, called when the property is read
+    pub getters: HashMap<String, Rc<LoxFunction>>,
+    // `set name(value) { ... }`, called when the property is assigned
+    pub setters: HashMap<String, Rc<LoxFunction>>,
+    // `class name() { ... }`
+    pub statics: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    // LoxClass.findMethod: jlox/LoxClass.java L20
+    // each of these looks in the superclass when this class doesn't have one
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.find(name, |class| &class.methods)
+    }
+
+    pub fn find_getter(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.find(name, |class| &class.getters)
+    }
+
+    pub fn find_setter(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.find(name, |class| &class.setters)
+    }
+
+    pub fn find_static(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.find(name, |class| &class.statics)
+    }
+
+    fn find(&self, name: &str, table: fn(&LoxClass) -> &HashMap<String, Rc<LoxFunction>>) -> Option<Rc<LoxFunction>> {
+        match table(self).get(name) {
+            Some(function) => Some(Rc::clone(function)),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find(name, table)),
+        }
+    }
+
+    // LoxClass.arity: jlox/LoxClass.java L44, the initializer's
+    pub fn min_arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.min_arity())
+    }
+
+    pub fn max_arity(&self) -> Option<usize> {
+        self.find_method("init").map_or(Some(0), |init| init.max_arity())
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -0,0 +1,136 @@
+// maps to jlox-java file jlox/LoxFunction.java
+// a function declared in Lox, or a lambda, with the environment it was made in
+use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
+use crate::expr::Value;
+use crate::token::Token;
+use crate::stmt::FunctionDecl;
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, Unwind};
+use crate::runtime_error::RuntimeError;
+
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionDecl>,
+    pub closure: Rc<RefCell<Environment>>,
+    // a class's init, which always gives back `this`
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    // LoxFunction.bind: jlox/LoxFunction.java L21
+    // a method with `this` defined around it, for an instance, or a class when it's static
+    pub fn bind(&self, this: Value) -> LoxFunction {
+        let mut environment = Environment::from(&self.closure);
+        environment.define("this", this);
+        LoxFunction {
+            declaration: Rc::clone(&self.declaration),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    // the params without defaults have to be passed
+    pub fn min_arity(&self) -> usize {
+        self.declaration.params.iter().filter(|param| param.default.is_none()).count()
+    }
+
+    // None when a rest parameter takes any number more
+    pub fn max_arity(&self) -> Option<usize> {
+        match self.declaration.rest {
+            Some(_) => None,
+            None => Some(self.declaration.params.len()),
+        }
+    }
+
+    // what a stack trace calls it
+    pub fn name(&self) -> String {
+        match &self.declaration.name {
+            Some(name) => name.lexeme.clone(),
+            None => "lambda".to_string(),
+        }
+    }
+
+    // arguments passed by name have to name a param that wasn't passed positionally,
+    // and with them every param without a default still has to be passed somehow
+    pub fn check_named(&self, paren: &Token, positional: usize, named: &[(Token, Value)]) -> Result<(), RuntimeError> {
+        if named.is_empty() { return Ok(()); }
+        let params = &self.declaration.params;
+        for (name, _) in named {
+            match params.iter().position(|param| param.name.lexeme == name.lexeme) {
+                Some(i) if i < positional => {
+                    return Err(RuntimeError::new(name, &format!("Argument '{}' was already passed by position.", name.lexeme)));
+                },
+                Some(_) => {},
+                None => {
+                    return Err(RuntimeError::new(name, &format!("{} has no parameter named '{}'.", self.name(), name.lexeme)));
+                },
+            }
+        }
+        let missing = params.iter().enumerate().find(|(i, param)| {
+            *i >= positional && param.default.is_none() && !named.iter().any(|(name, _)| name.lexeme == param.name.lexeme)
+        });
+        if let Some((_, param)) = missing {
+            return Err(RuntimeError::new(paren, &format!("Missing argument '{}'.", param.name.lexeme)));
+        }
+        Ok(())
+    }
+
+    pub fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.call_named(interpreter, arguments, vec![])
+    }
+
+    // LoxFunction.call: jlox/LoxFunction.java L27
+    // the interpreter has already checked the number of arguments, and check_named the names
+    pub fn call_named(&self, interpreter: &mut Interpreter, mut arguments: Vec<Value>, named: Vec<(Token, Value)>) -> Result<Value, RuntimeError> {
+        let environment = Rc::new(RefCell::new(Environment::from(&self.closure)));
+        let params = &self.declaration.params;
+        let extra = arguments.split_off(params.len().min(arguments.len()));
+        let mut arguments: Vec<Option<Value>> = arguments.into_iter().map(Some).collect();
+        arguments.resize_with(params.len(), || None);
+        for (name, value) in named {
+            if let Some(i) = params.iter().position(|param| param.name.lexeme == name.lexeme) {
+                arguments[i] = Some(value);
+            }
+        }
+        for (param, argument) in params.iter().zip(arguments) {
+            let value = match (argument, &param.default) {
+                (Some(argument), _) => argument,
+                // defaults see the params before them, e.g. fun f(a, b = a * 2)
+                (None, Some(default)) => interpreter.evaluate_in(default, Rc::clone(&environment))?,
+                (None, None) => Value::Nil,
+            };
+            environment.borrow_mut().define(&param.name.lexeme, value);
+        }
+        if let Some(rest) = &self.declaration.rest {
+            environment.borrow_mut().define(&rest.lexeme, Value::from(extra));
+        }
+
+        let result = match interpreter.execute_block(&self.declaration.body, environment) {
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+            // break and continue can't leave a function, the parser sees to that
+            _ => Value::Nil,
+        };
+        if self.is_initializer {
+            return Ok(self.closure.borrow().lookup("this").unwrap_or(Value::Nil));
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// LoxFunction.toString: jlox/LoxFunction.java L45
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.declaration.name {
+            Some(name) => write!(f, "<fn {}>", name.lexeme),
+            None => write!(f, "<fn>"),
+        }
+    }
+}
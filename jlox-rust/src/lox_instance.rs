@@ -0,0 +1,26 @@
+// maps to jlox-java file jlox/LoxInstance.java
+// property lookups need the interpreter, for getters and binding `this`,
+// so they live in Interpreter::get_property and set_property
+use std::fmt;
+use std::rc::Rc;
+use std::collections::HashMap;
+use crate::expr::Value;
+use crate::lox_class::LoxClass;
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> LoxInstance {
+        LoxInstance { class, fields: HashMap::new() }
+    }
+}
+
+// fields can hold the instance itself, so Debug doesn't print them
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,27 @@
+// not in jlox: what `import "path" as name;` binds name to.
+// a module's top level declarations live in its own global environment,
+// which encloses nothing but the natives, and they're read with name.declaration
+use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
+use crate::expr::Value;
+use crate::environment::Environment;
+
+pub struct LoxModule {
+    // the path as the import wrote it
+    pub name: String,
+    pub environment: Rc<RefCell<Environment>>,
+}
+
+impl LoxModule {
+    // one of the module's own declarations, not the natives it can see
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.environment.borrow().get_here(name)
+    }
+}
+
+impl fmt::Debug for LoxModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
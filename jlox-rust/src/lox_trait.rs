@@ -0,0 +1,21 @@
+// not in jlox: a named set of methods that classes mix in with `class A < B with T`.
+// a trait can't be called or have fields, it's only ever copied into classes
+use std::fmt;
+use std::rc::Rc;
+use std::collections::HashMap;
+use crate::lox_function::LoxFunction;
+
+pub struct LoxTrait {
+    pub name: String,
+    // the same tables as a LoxClass
+    pub methods: HashMap<String, Rc<LoxFunction>>,
+    pub getters: HashMap<String, Rc<LoxFunction>>,
+    pub setters: HashMap<String, Rc<LoxFunction>>,
+    pub statics: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl fmt::Debug for LoxTrait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<trait {}>", self.name)
+    }
+}
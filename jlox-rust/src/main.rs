@@ -6,19 +6,110 @@ mod token;
 mod expr;
 mod stmt;
 mod parser;
+mod resolver;
 // mod ast_printer;
 mod interpreter;
 mod environment;
+mod runtime_error;
+mod lox_callable;
+mod lox_function;
+mod lox_class;
+mod lox_instance;
+mod lox_trait;
+mod lox_module;
+mod math;
+mod strings;
+mod lists;
+mod maps;
+mod io;
+#[cfg(feature = "serde")]
+mod json;
 
 use crate::lox::Lox;
+use crate::interpreter::Limits;
+use std::time::{Duration, Instant};
+use std::thread;
+
+// the depth limits are sized for a main thread stack this big
+const MAIN_STACK_MB: usize = 8;
+
+const USAGE: &str = "Usage: jlox [--max-steps=N] [--max-call-depth=N] [--timeout-ms=N] [--stack-mb=N] [--no-io] [--strict-division] [--strict] [script [args...]]";
+
 // Lox.main: jlox/Lox.java L14
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // flags come before the script, everything after it is the script's `args`
+    // budget flags: --max-steps=N --max-call-depth=N --timeout-ms=N
+    // --stack-mb=N runs the script on a thread with a bigger stack, for deeper recursion
+    // --no-io takes away the file, stdin, environment and exit natives
+    // --strict-division makes division and modulo by zero runtime errors, not infinity or NaN
+    // --strict checks for undeclared globals, redefined globals and unused locals before
+    // running, which a script can also ask for with "use strict"; as its first statement
+    let mut limits = Limits::default();
+    let mut stack_mb = None;
+    let mut allow_io = true;
+    let mut strict_division = false;
+    let mut strict = false;
+    let mut script = None;
+    let mut args: Vec<String> = vec![];
+    for arg in env::args().skip(1) {
+	if script.is_some() {
+	    args.push(arg);
+	} else if arg == "--no-io" {
+	    allow_io = false;
+	} else if arg == "--strict-division" {
+	    strict_division = true;
+	} else if arg == "--strict" {
+	    strict = true;
+	} else if let Some(n) = arg.strip_prefix("--stack-mb=") {
+	    stack_mb = Some(parse_flag::<usize>(&arg, n));
+	} else if let Some(n) = arg.strip_prefix("--max-steps=") {
+	    limits.max_steps = Some(parse_flag(&arg, n));
+	} else if let Some(n) = arg.strip_prefix("--max-call-depth=") {
+	    limits.max_call_depth = Some(parse_flag(&arg, n));
+	} else if let Some(n) = arg.strip_prefix("--timeout-ms=") {
+	    limits.deadline = Some(Instant::now() + Duration::from_millis(parse_flag(&arg, n)));
+	} else if arg.starts_with("--") {
+	    println!("{}", USAGE);
+	    std::process::exit(64);
+	} else {
+	    script = Some(arg);
+	}
+    }
+
+    match stack_mb {
+	Some(mb) if mb > MAIN_STACK_MB => {
+	    let runner = thread::Builder::new()
+		.stack_size(mb * 1024 * 1024)
+		.spawn(move || run(script, args, limits, allow_io, strict_division, strict, mb / MAIN_STACK_MB))
+		.expect("couldn't spawn the interpreter thread");
+	    // a panic on the thread has already been printed, just pass on the failure
+	    if runner.join().is_err() { std::process::exit(70); }
+	},
+	_ => run(script, args, limits, allow_io, strict_division, strict, 1)
+    }
+}
+
+fn run(script: Option<String>, args: Vec<String>, limits: Limits, allow_io: bool, strict_division: bool, strict: bool, depth_factor: usize) {
     let mut lox = Lox::new();
-    match args.len() {
-	1 => lox.run_prompt(),
-	2 => lox.run_file(&args[1]),
-	_ => println!("Usage: jlox [script]")
+    lox.set_limits(limits);
+    if !allow_io { lox.allow_io(false); }
+    lox.strict_division(strict_division);
+    lox.strict(strict);
+    lox.scale_max_depth(depth_factor);
+    lox.set_args(args);
+    match script {
+	None => lox.run_prompt(),
+	Some(path) => lox.run_file(&path),
+    }
+}
+
+fn parse_flag<T: std::str::FromStr>(arg: &str, n: &str) -> T {
+    match n.parse() {
+	Ok(n) => n,
+	Err(_) => {
+	    eprintln!("Invalid number in {}", arg);
+	    std::process::exit(64);
+	}
     }
 }
 
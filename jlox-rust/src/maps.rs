@@ -0,0 +1,33 @@
+// map natives, defined as globals
+// keys come back in order: numbers first, then strings
+use std::rc::Rc;
+use std::collections::BTreeMap;
+use crate::expr::{Value, MapKey, Contents};
+use crate::environment::Environment;
+
+pub fn define_natives(globals: &mut Environment) {
+    globals.define_native("keys", 1, |args| {
+        let keys = map(args, 0, "keys")?.borrow().keys().map(MapKey::to_value).collect::<Vec<Value>>();
+        Ok(Value::from(keys))
+    });
+    globals.define_native("values", 1, |args| {
+        let values = map(args, 0, "values")?.borrow().values().cloned().collect::<Vec<Value>>();
+        Ok(Value::from(values))
+    });
+    globals.define_native("has", 2, |args| {
+        let key = MapKey::from_value(&args[1])?;
+        Ok(Value::from(map(args, 0, "has")?.borrow().contains_key(&key)))
+    });
+    // gives back the removed value, or nil if it wasn't there
+    globals.define_native("delete", 2, |args| {
+        let key = MapKey::from_value(&args[1])?;
+        Ok(map(args, 0, "delete")?.borrow_mut().remove(&key).unwrap_or(Value::Nil))
+    });
+}
+
+fn map<'a>(args: &'a [Value], position: usize, name: &str) -> Result<&'a Rc<Contents<BTreeMap<MapKey, Value>>>, String> {
+    match &args[position] {
+        Value::Map(entries) => Ok(entries),
+        _ => Err(format!("Argument {} to {} must be a map.", position + 1, name))
+    }
+}
@@ -0,0 +1,58 @@
+// math natives, defined as globals
+use std::f64::consts;
+use crate::expr::Value;
+use crate::environment::Environment;
+
+pub fn define_natives(globals: &mut Environment) {
+    globals.define("PI", Value::Number(consts::PI));
+    globals.define("E", Value::Number(consts::E));
+    globals.define("INFINITY", Value::Number(f64::INFINITY));
+    globals.define("NAN", Value::Number(f64::NAN));
+
+    globals.define_native("sqrt", 1, |args| Ok(Value::from(number(args, 0, "sqrt")?.sqrt())));
+    globals.define_native("cbrt", 1, |args| Ok(Value::from(number(args, 0, "cbrt")?.cbrt())));
+    globals.define_native("abs", 1, |args| Ok(Value::from(number(args, 0, "abs")?.abs())));
+    globals.define_native("floor", 1, |args| Ok(Value::from(number(args, 0, "floor")?.floor())));
+    globals.define_native("ceil", 1, |args| Ok(Value::from(number(args, 0, "ceil")?.ceil())));
+    globals.define_native("round", 1, |args| Ok(Value::from(number(args, 0, "round")?.round())));
+    globals.define_native("trunc", 1, |args| Ok(Value::from(number(args, 0, "trunc")?.trunc())));
+    globals.define_native("sign", 1, |args| {
+        let n = number(args, 0, "sign")?;
+        // f64::signum gives 1 for 0, which isn't what anyone wants here
+        Ok(Value::from(if n == 0.0 || n.is_nan() { n } else { n.signum() }))
+    });
+
+    globals.define_native("sin", 1, |args| Ok(Value::from(number(args, 0, "sin")?.sin())));
+    globals.define_native("cos", 1, |args| Ok(Value::from(number(args, 0, "cos")?.cos())));
+    globals.define_native("tan", 1, |args| Ok(Value::from(number(args, 0, "tan")?.tan())));
+    globals.define_native("asin", 1, |args| Ok(Value::from(number(args, 0, "asin")?.asin())));
+    globals.define_native("acos", 1, |args| Ok(Value::from(number(args, 0, "acos")?.acos())));
+    globals.define_native("atan", 1, |args| Ok(Value::from(number(args, 0, "atan")?.atan())));
+    globals.define_native("atan2", 2, |args| {
+        Ok(Value::from(number(args, 0, "atan2")?.atan2(number(args, 1, "atan2")?)))
+    });
+
+    globals.define_native("exp", 1, |args| Ok(Value::from(number(args, 0, "exp")?.exp())));
+    // natural log, like javascript's Math.log
+    globals.define_native("log", 1, |args| Ok(Value::from(number(args, 0, "log")?.ln())));
+    globals.define_native("log10", 1, |args| Ok(Value::from(number(args, 0, "log10")?.log10())));
+    globals.define_native("log2", 1, |args| Ok(Value::from(number(args, 0, "log2")?.log2())));
+    globals.define_native("pow", 2, |args| {
+        Ok(Value::from(number(args, 0, "pow")?.powf(number(args, 1, "pow")?)))
+    });
+    globals.define_native("min", 2, |args| {
+        Ok(Value::from(number(args, 0, "min")?.min(number(args, 1, "min")?)))
+    });
+    globals.define_native("max", 2, |args| {
+        Ok(Value::from(number(args, 0, "max")?.max(number(args, 1, "max")?)))
+    });
+}
+
+// the type check every math native needs
+fn number(args: &[Value], index: usize, name: &str) -> Result<f64, String> {
+    match args[index] {
+        Value::Number(n) => Ok(n),
+        _ if args.len() == 1 => Err(format!("Argument to {} must be a number.", name)),
+        _ => Err(format!("Arguments to {} must be numbers.", name))
+    }
+}
@@ -4,155 +4,818 @@ use crate::stmt::*;
 use crate::token_type::TokenType;
 use crate::token_type::TokenType::*;
 use crate::token::Token;
+use std::rc::Rc;
+use std::cell::Cell;
 // use crate::ast_printer::*;
 
+// deepest nesting of expressions and blocks before parsing gives up
+// each level of parens is a trip through every precedence function, which is
+// ~10KB of stack in a debug build, so this keeps well inside the main thread's 8MB
+pub const DEFAULT_MAX_DEPTH: usize = 255;
+
+// jlox's ParseError: the error is reported when it's made, this just unwinds to declaration
+pub struct ParseError;
+
+type ParseResult<T> = Result<T, ParseError>;
+
 pub struct Parser {
     pub tokens: Vec<Token>,
     current: usize,
+    depth: usize,
+    pub max_depth: usize,
+    // how many loops we're inside, so break and continue outside one are parse errors
+    // a function body starts again from 0
+    loop_depth: usize,
+    // same for return outside a function
+    function_depth: usize,
+    pub had_error: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
-	Parser { tokens, current: 0 }
+	Parser { tokens, current: 0, depth: 0, max_depth: DEFAULT_MAX_DEPTH, loop_depth: 0, function_depth: 0, had_error: false }
     }
 
     pub fn parse(&mut self) -> Vec<Stmt> {
 	let mut stmts: Vec<Stmt> = vec![];
 	while !self.is_at_end() {
-	    stmts.push(self.declaration());
+	    if let Some(stmt) = self.declaration() {
+		stmts.push(stmt);
+	    }
 	}
 	stmts
     }
 
-    fn declaration(&mut self) -> Stmt {
-	if self.mtch(vec![TokenType::Var]) {
+    fn declaration(&mut self) -> Option<Stmt> {
+	let depth = self.depth;
+	let loop_depth = self.loop_depth;
+	let function_depth = self.function_depth;
+	// `fun (` starts a lambda in an expression statement, not a declaration
+	let result = if self.check(&Fun) && self.check_next(&Identifier) {
+	    self.advance();
+	    self.function("function")
+	} else if self.mtch(vec![TokenType::Class]) {
+	    self.class_declaration()
+	} else if self.mtch(vec![Trait]) {
+	    self.trait_declaration()
+	} else if self.mtch(vec![Import]) {
+	    self.import_statement()
+	} else if self.starts_from_import() {
+	    self.advance();
+	    self.import_from_statement()
+	} else if self.mtch(vec![TokenType::Var]) {
 	    self.var_declaration()
+	} else if self.mtch(vec![Const]) {
+	    self.const_declaration()
 	} else {
 	    self.statement()
+	};
+	match result {
+	    Ok(stmt) => Some(stmt),
+	    Err(ParseError) => {
+		// unwinding skipped the decrements on the way out
+		self.depth = depth;
+		self.loop_depth = loop_depth;
+		self.function_depth = function_depth;
+		self.synchronize();
+		None
+	    }
 	}
     }
 
-    fn statement(&mut self) -> Stmt {
+    fn statement(&mut self) -> ParseResult<Stmt> {
 	if self.mtch(vec![TokenType::Print]) {
 	    self.print_statement()
-	} else if self.mtch(vec![TokenType::LeftBrace]) {
+	} else if self.mtch(vec![If]) {
+	    self.if_statement()
+	} else if self.mtch(vec![While]) {
+	    self.while_statement()
+	} else if self.mtch(vec![For]) {
+	    self.for_statement()
+	} else if self.mtch(vec![Break, Continue]) {
+	    self.jump_statement()
+	} else if self.mtch(vec![Return]) {
+	    self.return_statement()
+	} else if self.mtch(vec![Match]) {
+	    self.match_statement()
+	} else if self.mtch(vec![Throw]) {
+	    self.throw_statement()
+	} else if self.mtch(vec![Try]) {
+	    self.try_statement()
+	} else if self.check(&LeftBrace) && !self.starts_map() {
+	    self.advance();
 	    self.block_statement()
 	} else {
 	    self.expression_statement()
 	}
     }
 
-    fn var_declaration(&mut self) -> Stmt {
-	let name = self.consume(&Identifier, "Expect variable name").unwrap();
+    // Parser.classDeclaration: jlox/Parser.java L63, `class` already matched
+    fn class_declaration(&mut self) -> ParseResult<Stmt> {
+	let name = self.consume(&Identifier, "Expect class name.")?;
+
+	let mut superclass = None;
+	if self.mtch(vec![Less]) {
+	    let name = self.consume(&Identifier, "Expect superclass name.")?;
+	    superclass = Some(Box::new(Variable { name, depth: Cell::new(None) }));
+	}
+
+	let mut traits = vec![];
+	if self.mtch(vec![With]) {
+	    loop {
+		let name = self.consume(&Identifier, "Expect trait name.")?;
+		traits.push(Variable { name, depth: Cell::new(None) });
+		if !self.mtch(vec![TokenType::Comma]) { break; }
+	    }
+	}
+
+	let methods = self.members("class")?;
+	Ok(Stmt::Class { name, superclass, traits, methods })
+    }
+
+    // trait name { methods }, `trait` already matched
+    fn trait_declaration(&mut self) -> ParseResult<Stmt> {
+	let name = self.consume(&Identifier, "Expect trait name.")?;
+	let methods = self.members("trait")?;
+	Ok(Stmt::Trait { name, methods })
+    }
+
+    // { methods } of a class or trait
+    fn members(&mut self, kind: &str) -> ParseResult<Vec<Method>> {
+	self.consume(&LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+	let mut methods = vec![];
+	while !self.check(&RightBrace) && !self.is_at_end() {
+	    methods.push(self.method()?);
+	}
+	self.consume(&RightBrace, &format!("Expect '}}' after {} body.", kind))?;
+	Ok(methods)
+    }
+
+    // one member of a class body:
+    //   name(params) {}        a method
+    //   class name(params) {}  a static method, called on the class
+    //   name {}                a getter
+    //   set name(value) {}     a setter
+    // `set` is only special before another name, so a method can still be called set
+    fn method(&mut self) -> ParseResult<Method> {
+	let kind = if self.mtch(vec![TokenType::Class]) {
+	    MethodKind::Static
+	} else if self.check(&Identifier) && self.peek().lexeme == "set" && self.check_next(&Identifier) {
+	    self.advance();
+	    MethodKind::Setter
+	} else if self.check(&Identifier) && self.check_next(&LeftBrace) {
+	    MethodKind::Getter
+	} else {
+	    MethodKind::Method
+	};
+	let name = self.consume(&Identifier, "Expect method name.")?;
+
+	let declaration = if kind == MethodKind::Getter {
+	    self.advance();
+	    let body = self.function_block()?;
+	    Rc::new(FunctionDecl { name: Some(name), params: vec![], rest: None, body })
+	} else {
+	    let declaration = self.function_body(Some(name.clone()), "method")?;
+	    if kind == MethodKind::Setter && (declaration.params.len() != 1 || declaration.rest.is_some()) {
+		// reported, but no need to unwind: the parser isn't confused
+		self.error(&name, "A setter must have exactly one parameter.");
+	    }
+	    declaration
+	};
+	Ok(Method { kind, declaration })
+    }
+
+    // import "path" as name;, `import` already matched
+    // `as` is only special here, so it can still be a variable name
+    fn import_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.import_keyword();
+	let path = self.module_path()?;
+	let as_ = self.consume(&Identifier, "Expect 'as' after module path.")?;
+	if as_.lexeme != "as" {
+	    return Err(self.error(&as_, "Expect 'as' after module path."));
+	}
+	let name = self.consume(&Identifier, "Expect module name after 'as'.")?;
+	self.consume(&Semicolon, "Expect ';' after import.")?;
+	Ok(Stmt::Import { keyword, path, imported: Imported::Module(name) })
+    }
+
+    // from "path" import a, b;, `from` already matched
+    fn import_from_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.import_keyword();
+	let path = self.module_path()?;
+	self.consume(&Import, "Expect 'import' after module path.")?;
+	let mut names = vec![];
+	loop {
+	    names.push(self.consume(&Identifier, "Expect name to import.")?);
+	    if !self.mtch(vec![TokenType::Comma]) { break; }
+	}
+	self.consume(&Semicolon, "Expect ';' after import.")?;
+	Ok(Stmt::Import { keyword, path, imported: Imported::Names(names) })
+    }
+
+    // paths are relative to the file being run, which a function called from
+    // somewhere else wouldn't know, so imports have to be at the top level
+    fn import_keyword(&mut self) -> Token {
+	let keyword = self.previous();
+	if self.function_depth > 0 {
+	    // reported, but no need to unwind: the parser isn't confused
+	    self.error(&keyword, "Can only import at the top level of a file.");
+	}
+	keyword
+    }
+
+    fn module_path(&mut self) -> ParseResult<String> {
+	match self.peek().type_ {
+	    STRING { literal } => {
+		self.advance();
+		Ok(literal)
+	    },
+	    _ => Err(self.error(&self.peek(), "Expect module path string.")),
+	}
+    }
+
+    // `from` is only special before a string
+    fn starts_from_import(&self) -> bool {
+	self.check(&Identifier) && self.peek().lexeme == "from"
+	    && matches!(self.tokens.get(self.current + 1).map(|token| &token.type_), Some(STRING { .. }))
+    }
+
+    // Parser.function: jlox/Parser.java L78, `fun` already matched
+    fn function(&mut self, kind: &str) -> ParseResult<Stmt> {
+	let name = self.consume(&Identifier, &format!("Expect {} name.", kind))?;
+	let declaration = self.function_body(Some(name), kind)?;
+	Ok(Stmt::Function { declaration })
+    }
+
+    // (params) { body }, for declarations and `fun` lambdas
+    fn function_body(&mut self, name: Option<Token>, kind: &str) -> ParseResult<Rc<FunctionDecl>> {
+	self.consume(&LeftParen, &format!("Expect '(' after {} name.", kind))?;
+	let (params, rest) = self.parameters()?;
+
+	self.consume(&LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+	let body = self.function_block()?;
+	Ok(Rc::new(FunctionDecl { name, params, rest, body }))
+    }
+
+    // a, b = default, ...rest) after the '(', up to and including the ')'
+    // once a parameter has a default the ones after it need one too,
+    // and a rest parameter has to be the last
+    fn parameters(&mut self) -> ParseResult<(Vec<Param>, Option<Token>)> {
+	let mut params: Vec<Param> = vec![];
+	let mut rest = None;
+	while !self.check(&RightParen) {
+	    if params.len() >= 255 {
+		self.error(&self.peek(), "Cannot have more than 255 parameters.");
+	    }
+	    if self.mtch(vec![DotDotDot]) {
+		rest = Some(self.consume(&Identifier, "Expect rest parameter name.")?);
+		if self.check(&TokenType::Comma) {
+		    return Err(self.error(&self.peek(), "Rest parameter must be last."));
+		}
+		break;
+	    }
+	    let name = self.consume(&Identifier, "Expect parameter name.")?;
+	    let mut default = None;
+	    if self.mtch(vec![Equal]) {
+		default = Some(self.element()?);
+	    } else if params.last().is_some_and(|param| param.default.is_some()) {
+		// reported, but no need to unwind: the parser isn't confused
+		self.error(&name, "Parameter without a default can't follow one with a default.");
+	    }
+	    params.push(Param { name, default });
+	    if !self.mtch(vec![TokenType::Comma]) { break; }
+	}
+	self.consume(&RightParen, "Expect ')' after parameters.")?;
+	Ok((params, rest))
+    }
+
+    // a function's block, where return is allowed and break and continue
+    // can't reach a loop outside it
+    fn function_block(&mut self) -> ParseResult<Vec<Stmt>> {
+	let loop_depth = self.loop_depth;
+	self.loop_depth = 0;
+	self.function_depth += 1;
+	let body = self.block()?;
+	self.function_depth -= 1;
+	self.loop_depth = loop_depth;
+	Ok(body)
+    }
+
+    fn var_declaration(&mut self) -> ParseResult<Stmt> {
+	let name = self.consume(&Identifier, "Expect variable name")?;
 
 	let mut initializer = Expr::Literal { value: Value::Nil };
 	if self.mtch(vec![Equal]) {
-	    initializer = self.expression();
+	    initializer = self.element()?;
+	}
+
+	self.consume(&Semicolon, "Expect ';' after variable declaration")?;
+	Ok(Stmt::Var { name, initializer: Box::new(initializer), constant: false })
+    }
+
+    // like var, but it has to have a value, since it can never get one later
+    fn const_declaration(&mut self) -> ParseResult<Stmt> {
+	let name = self.consume(&Identifier, "Expect constant name.")?;
+	self.consume(&Equal, "Expect '=' after constant name.")?;
+	let initializer = self.element()?;
+	self.consume(&Semicolon, "Expect ';' after constant declaration.")?;
+	Ok(Stmt::Var { name, initializer: Box::new(initializer), constant: true })
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.previous();
+	let value = self.expression()?;
+	self.consume(&Semicolon, "Expect ';' after value.")?;
+	Ok(Stmt::Print { keyword, expr: Box::new(value) })
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+	self.consume(&LeftParen, "Expect '(' after 'if'.")?;
+	let condition = self.expression()?;
+	self.consume(&RightParen, "Expect ')' after if condition.")?;
+
+	let then_branch = Box::new(self.nested_statement()?);
+	let mut else_branch = None;
+	if self.mtch(vec![Else]) {
+	    else_branch = Some(Box::new(self.nested_statement()?));
+	}
+
+	Ok(Stmt::If { condition: Box::new(condition), then_branch, else_branch })
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+	self.consume(&LeftParen, "Expect '(' after 'while'.")?;
+	let condition = self.expression()?;
+	self.consume(&RightParen, "Expect ')' after condition.")?;
+	let body = self.loop_body()?;
+
+	Ok(Stmt::While { condition: Box::new(condition), body: Box::new(body), increment: None })
+    }
+
+    // desugars to { initializer; while (condition) body } like jlox,
+    // except the increment stays on the While so `continue` doesn't skip it
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+	self.consume(&LeftParen, "Expect '(' after 'for'.")?;
+	if self.check(&Var) && self.tokens.get(self.current + 2).map(|t| &t.type_) == Some(&In) {
+	    return self.for_in_statement();
+	}
+
+	let initializer = if self.mtch(vec![Semicolon]) {
+	    None
+	} else if self.mtch(vec![Var]) {
+	    Some(self.var_declaration()?)
+	} else {
+	    Some(self.expression_statement()?)
+	};
+
+	let mut condition = Expr::Literal { value: Value::True };
+	if !self.check(&Semicolon) {
+	    condition = self.expression()?;
 	}
+	self.consume(&Semicolon, "Expect ';' after loop condition.")?;
 
-	self.consume(&Semicolon, "Expect ';' after variable declaration");
-	Stmt::Var { name, initializer: Box::new(initializer) } 	
+	let mut increment = None;
+	if !self.check(&RightParen) {
+	    increment = Some(Box::new(self.expression()?));
+	}
+	self.consume(&RightParen, "Expect ')' after for clauses.")?;
+
+	let body = self.loop_body()?;
+	let mut stmt = Stmt::While { condition: Box::new(condition), body: Box::new(body), increment };
+
+	if let Some(initializer) = initializer {
+	    stmt = Stmt::Block { stmts: vec![initializer, stmt] };
+	}
+	Ok(stmt)
     }
 
-    fn print_statement(&mut self) -> Stmt {
-	let value = self.expression();
-	self.consume(&Semicolon, "Expect ';' after value.");
-	Stmt::Print { expr: Box::new(value) }	
+    // for (var name in iterable) body, after the '('
+    fn for_in_statement(&mut self) -> ParseResult<Stmt> {
+	self.advance();
+	let name = self.consume(&Identifier, "Expect variable name.")?;
+	let keyword = self.consume(&In, "Expect 'in' after variable name.")?;
+	let iterable = self.expression()?;
+	self.consume(&RightParen, "Expect ')' after for clauses.")?;
+	let body = self.loop_body()?;
+	Ok(Stmt::ForIn { name, keyword, iterable: Box::new(iterable), body: Box::new(body) })
+    }
+
+    fn loop_body(&mut self) -> ParseResult<Stmt> {
+	self.loop_depth += 1;
+	let body = self.nested_statement()?;
+	self.loop_depth -= 1;
+	Ok(body)
+    }
+
+    // break or continue, already matched
+    fn jump_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.previous();
+	if self.loop_depth == 0 {
+	    // reported, but no need to unwind: the parser isn't confused
+	    self.error(&keyword, &format!("Can't use '{}' outside of a loop.", keyword.lexeme));
+	}
+	self.consume(&Semicolon, &format!("Expect ';' after '{}'.", keyword.lexeme))?;
+	match keyword.type_ {
+	    Break => Ok(Stmt::Break { keyword }),
+	    _ => Ok(Stmt::Continue { keyword }),
+	}
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.previous();
+	if self.function_depth == 0 {
+	    // reported, but no need to unwind: the parser isn't confused
+	    self.error(&keyword, "Can't return from top-level code.");
+	}
+	let mut value = None;
+	if !self.check(&Semicolon) {
+	    value = Some(Box::new(self.expression()?));
+	}
+	self.consume(&Semicolon, "Expect ';' after return value.")?;
+	Ok(Stmt::Return { keyword, value })
     }
 
-    fn block_statement(&mut self) -> Stmt {
+    fn match_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.previous();
+	let value = self.expression()?;
+	self.consume(&LeftBrace, "Expect '{' after match value.")?;
+
+	let mut arms = vec![];
+	let mut has_default = false;
+	while !self.check(&RightBrace) && !self.is_at_end() {
+	    let mut patterns = vec![self.pattern()?];
+	    while self.mtch(vec![Pipe]) {
+		patterns.push(self.pattern()?);
+	    }
+	    if patterns.len() > 1 {
+		let binding = patterns.iter().find_map(|pattern| match pattern {
+		    Pattern::Binding(name) => Some(name.clone()),
+		    _ => None
+		});
+		if let Some(name) = binding {
+		    // reported, but no need to unwind: the parser isn't confused
+		    self.error(&name, "Can't bind a name in a pattern with alternatives.");
+		}
+	    }
+
+	    let mut guard = None;
+	    if self.mtch(vec![If]) {
+		guard = Some(self.expression()?);
+	    }
+	    self.consume(&Arrow, "Expect '=>' after pattern.")?;
+
+	    // a block, or an expression and a comma unless it's the last arm
+	    let body = if self.check(&LeftBrace) && !self.starts_map() {
+		self.advance();
+		let body = self.block_statement()?;
+		self.mtch(vec![TokenType::Comma]);
+		body
+	    } else {
+		let expr = self.element()?;
+		if !self.check(&RightBrace) {
+		    self.consume(&TokenType::Comma, "Expect ',' after match arm.")?;
+		}
+		Stmt::Expression { expr: Box::new(expr) }
+	    };
+
+	    if guard.is_none() && patterns.iter().any(|pattern| !matches!(pattern, Pattern::Literal(_))) {
+		has_default = true;
+	    }
+	    arms.push(MatchArm { patterns, guard, body: Box::new(body) });
+	}
+	self.consume(&RightBrace, "Expect '}' after match arms.")?;
+
+	if !has_default {
+	    crate::lox::warning(&keyword, "Match has no default arm, so a value can match nothing.");
+	}
+	Ok(Stmt::Match { keyword, value: Box::new(value), arms })
+    }
+
+    // a literal, possibly negative, _ or a name to bind
+    fn pattern(&mut self) -> ParseResult<Pattern> {
+	let negative = self.mtch(vec![Minus]);
+	let token = self.advance();
+	match token.type_ {
+	    Number { literal } => Ok(Pattern::Literal(Value::Number(if negative { -literal } else { literal }))),
+	    _ if negative => Err(self.error(&token, "Expect number after '-' in pattern.")),
+	    STRING { literal } => Ok(Pattern::Literal(Value::Strng(literal))),
+	    True => Ok(Pattern::Literal(Value::True)),
+	    False => Ok(Pattern::Literal(Value::False)),
+	    Nil => Ok(Pattern::Literal(Value::Nil)),
+	    Identifier if token.lexeme == "_" => Ok(Pattern::Wildcard),
+	    Identifier => Ok(Pattern::Binding(token)),
+	    _ => Err(self.error(&token, "Expect pattern."))
+	}
+    }
+
+    fn throw_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.previous();
+	let value = self.expression()?;
+	self.consume(&Semicolon, "Expect ';' after thrown value.")?;
+	Ok(Stmt::Throw { keyword, value: Box::new(value) })
+    }
+
+    fn try_statement(&mut self) -> ParseResult<Stmt> {
+	let keyword = self.previous();
+	self.consume(&LeftBrace, "Expect '{' after 'try'.")?;
+	let body = self.block()?;
+
+	let mut catch = None;
+	if self.mtch(vec![Catch]) {
+	    self.consume(&LeftParen, "Expect '(' after 'catch'.")?;
+	    let name = self.consume(&Identifier, "Expect exception variable name.")?;
+	    self.consume(&RightParen, "Expect ')' after exception variable.")?;
+	    self.consume(&LeftBrace, "Expect '{' after catch clause.")?;
+	    catch = Some((name, self.block()?));
+	}
+
+	let mut finally = None;
+	if self.mtch(vec![Finally]) {
+	    self.consume(&LeftBrace, "Expect '{' after 'finally'.")?;
+	    finally = Some(self.block()?);
+	}
+
+	if catch.is_none() && finally.is_none() {
+	    // reported, but no need to unwind: the parser isn't confused
+	    self.error(&keyword, "Expect 'catch' or 'finally' after try block.");
+	}
+	Ok(Stmt::Try { body, catch, finally })
+    }
+
+    // a statement inside another one, e.g. `if (a) if (b) ...`, which can nest
+    // as deep as blocks can
+    fn nested_statement(&mut self) -> ParseResult<Stmt> {
+	self.nest("Statement too deeply nested.")?;
+	let stmt = self.statement()?;
+	self.depth -= 1;
+	Ok(stmt)
+    }
+
+    fn block_statement(&mut self) -> ParseResult<Stmt> {
+	Ok(Stmt::Block { stmts: self.block()? })
+    }
+
+    // Parser.block: jlox/Parser.java L174, after the '{'
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+	self.nest("Block too deeply nested.")?;
 	let mut stmts = vec![];
 	while !self.check(&RightBrace) && !self.is_at_end() {
-	    stmts.push(self.declaration());
+	    if let Some(stmt) = self.declaration() {
+		stmts.push(stmt);
+	    }
 	}
-	self.consume(&RightBrace, "Expect '}' after block.");
-	Stmt::Block { stmts }
+	self.consume(&RightBrace, "Expect '}' after block.")?;
+	self.depth -= 1;
+	Ok(stmts)
+    }
+
+    fn expression_statement(&mut self) -> ParseResult<Stmt> {
+	let value = self.expression()?;
+	self.consume(&Semicolon, "Expect ';' after value.")?;
+	Ok(Stmt::Expression { expr: Box::new(value) })
     }
 
-    fn expression_statement(&mut self) -> Stmt {
-	let value = self.expression();
-	self.consume(&Semicolon, "Expect ';' after value.");
-	Stmt::Expression { expr: Box::new(value) }	
+    fn expression(&mut self) -> ParseResult<Expr> {
+	self.nest("Expression too deeply nested.")?;
+	let expr = self.comma()?;
+	self.depth -= 1;
+	Ok(expr)
     }
 
-    fn expression(&mut self) -> Expr {
-	self.assignment()
+    // an expression where a comma means the next one starts, not the comma operator:
+    // call arguments, list elements, map entries and var initializers
+    fn element(&mut self) -> ParseResult<Expr> {
+	self.nest("Expression too deeply nested.")?;
+	let expr = self.assignment()?;
+	self.depth -= 1;
+	Ok(expr)
+    }
+
+    // C's comma operator: evaluate both, keep the right
+    fn comma(&mut self) -> ParseResult<Expr> {
+	let mut expr = self.assignment()?;
+
+	while self.mtch(vec![TokenType::Comma]) {
+	    let right = self.assignment()?;
+	    expr = Expr::Comma { left: Box::new(expr), right: Box::new(right) };
+	}
+
+	Ok(expr)
     }
 
-    fn assignment(&mut self) ->  Expr {
-	let mut expr = self.equality();
+    // right associative, so a = b = 1 assigns 1 to b and then to a
+    fn assignment(&mut self) -> ParseResult<Expr> {
+	let mut expr = self.ternary()?;
 
 	if self.mtch(vec![Equal]) {
-	    // let equals = self.previous(); needed only if we're erroring with a token
-	    let value = self.equality();
+	    let equals = self.previous();
+	    let value = self.assignment_value()?;
 	    expr = match expr {
-		Variable { name } => Expr::Assign { name, value: Box::new(value) },
-		_ => { panic!("Invalid assignment target."); }
+		Variable { name, depth } => Expr::Assign { name, value: Box::new(value), depth },
+		Index { object, bracket, index } => SetIndex { object, bracket, index, value: Box::new(value) },
+		Get { object, name } => Set { object, name, value: Box::new(value) },
+		// reported, but no need to unwind: the parser isn't confused
+		_ => { self.error(&equals, "Invalid assignment target."); expr }
 	    };
+	} else if self.mtch(vec![PlusEqual, MinusEqual, StarEqual, SlashEqual, PercentEqual]) {
+	    let operator = self.previous();
+	    let value = self.assignment_value()?;
+	    expr = self.compound_assign(expr, operator, value, false);
+	}
+
+	Ok(expr)
+    }
+
+    fn assignment_value(&mut self) -> ParseResult<Expr> {
+	self.nest("Expression too deeply nested.")?;
+	let value = self.assignment();
+	self.depth -= 1;
+	value
+    }
+
+    // x op= value, ++x, x++: checks the target like plain assignment does
+    // the operator's type becomes the binary one it applies, e.g. += becomes Plus
+    fn compound_assign(&mut self, target: Expr, mut operator: Token, value: Expr, postfix: bool) -> Expr {
+	match target {
+	    Variable { .. } | Index { .. } | Get { .. } => {},
+	    _ => {
+		self.error(&operator, "Invalid assignment target.");
+		return target;
+	    }
+	}
+	operator.type_ = match operator.type_ {
+	    PlusEqual | PlusPlus => Plus,
+	    MinusEqual | MinusMinus => Minus,
+	    StarEqual => Star,
+	    SlashEqual => Slash,
+	    _ => Percent,
+	};
+	CompoundAssign { target: Box::new(target), operator, value: Box::new(value), postfix }
+    }
+
+    fn one() -> Expr {
+	Literal { value: Value::Number(1.0) }
+    }
+
+    // cond ? a : b, right associative, so a ? b : c ? d : e is a ? b : (c ? d : e)
+    fn ternary(&mut self) -> ParseResult<Expr> {
+	let condition = self.equality()?;
+
+	if self.mtch(vec![Question]) {
+	    // anything goes between ? and :, like C
+	    let then_branch = self.expression()?;
+	    self.consume(&Colon, "Expect ':' after then branch of conditional expression.")?;
+	    self.nest("Expression too deeply nested.")?;
+	    let else_branch = self.ternary()?;
+	    self.depth -= 1;
+	    return Ok(Ternary {
+		condition: Box::new(condition),
+		then_branch: Box::new(then_branch),
+		else_branch: Box::new(else_branch),
+	    });
 	}
 
-	expr
+	Ok(condition)
     }
 
-    fn equality(&mut self) -> Expr {
-	let mut expr = self.comparison();
+    fn equality(&mut self) -> ParseResult<Expr> {
+	let mut expr = self.comparison()?;
 
 	while self.mtch(vec![BangEqual, EqualEqual]) {
 	    let operator = self.previous();
-	    let right = self.comparison();
+	    let right = self.comparison()?;
 	    expr = Binary { left: Box::new(expr), operator, right: Box::new(right) };
 	}
 
-	expr
+	Ok(expr)
     }
 
-    fn comparison(&mut self) -> Expr {
-	let mut expr = self.addition();
+    fn comparison(&mut self) -> ParseResult<Expr> {
+	let mut expr = self.addition()?;
 
 	while self.mtch(vec![Greater, GreaterEqual, Less, LessEqual]) {
 	    let operator: Token = self.previous();
-	    let right = self.addition();
+	    let right = self.addition()?;
 	    expr = Binary { left: Box::new(expr), operator, right: Box::new(right) };
 	}
 
-	expr
+	Ok(expr)
     }
 
-    fn addition(&mut self) -> Expr {
-	let mut expr = self.multiplication();
+    fn addition(&mut self) -> ParseResult<Expr> {
+	let mut expr = self.multiplication()?;
 
 	while self.mtch(vec![Minus, Plus]) {
 	    let operator = self.previous();
-	    let right = self.multiplication();
+	    let right = self.multiplication()?;
 	    expr = Binary { left: Box::new(expr), operator, right: Box::new(right) };
 	}
 
-	expr
+	Ok(expr)
     }
 
-    fn multiplication(&mut self) -> Expr {
-	let mut expr = self.unary();
+    fn multiplication(&mut self) -> ParseResult<Expr> {
+	let mut expr = self.unary()?;
 
-	while self.mtch(vec![Slash, Star]) {
+	while self.mtch(vec![Slash, SlashSlash, Star, Percent]) {
 	    let operator = self.previous();
-	    let right = self.unary();
+	    let right = self.unary()?;
 	    expr = Binary { left: Box::new(expr), operator, right: Box::new(right) };
 	}
 
-	expr
+	Ok(expr)
     }
 
-    fn unary(&mut self) -> Expr {
+    fn unary(&mut self) -> ParseResult<Expr> {
 	if self.mtch(vec![Bang, Minus]) {
 	    let operator = self.previous();
-	    let right = self.unary();
-	    Unary { operator, right: Box::new(right) }
+	    self.nest("Expression too deeply nested.")?;
+	    let right = self.unary()?;
+	    self.depth -= 1;
+	    Ok(Unary { operator, right: Box::new(right) })
+	} else if self.mtch(vec![PlusPlus, MinusMinus]) {
+	    let operator = self.previous();
+	    self.nest("Expression too deeply nested.")?;
+	    let target = self.unary()?;
+	    self.depth -= 1;
+	    Ok(self.compound_assign(target, operator, Parser::one(), false))
 	} else {
-	    self.primary()
+	    self.exponent()
+	}
+    }
+
+    // right associative, and tighter than unary minus on its left: -2 ** 2 is -4,
+    // while the right operand can be unary: 2 ** -1
+    fn exponent(&mut self) -> ParseResult<Expr> {
+	let expr = self.call()?;
+
+	if self.mtch(vec![StarStar]) {
+	    let operator = self.previous();
+	    self.nest("Expression too deeply nested.")?;
+	    let right = self.unary()?;
+	    self.depth -= 1;
+	    return Ok(Binary { left: Box::new(expr), operator, right: Box::new(right) });
+	}
+
+	Ok(expr)
+    }
+
+    fn call(&mut self) -> ParseResult<Expr> {
+	let mut expr = self.primary()?;
+
+	loop {
+	    if self.mtch(vec![LeftParen]) {
+		expr = self.finish_call(expr)?;
+	    } else if self.mtch(vec![LeftBracket]) {
+		let index = self.expression()?;
+		let bracket = self.consume(&RightBracket, "Expect ']' after index.")?;
+		expr = Index { object: Box::new(expr), bracket, index: Box::new(index) };
+	    } else if self.mtch(vec![Dot]) {
+		let name = self.consume(&Identifier, "Expect property name after '.'.")?;
+		expr = Get { object: Box::new(expr), name };
+	    } else {
+		break;
+	    }
 	}
+
+	if self.mtch(vec![PlusPlus, MinusMinus]) {
+	    let operator = self.previous();
+	    expr = self.compound_assign(expr, operator, Parser::one(), true);
+	}
+
+	Ok(expr)
     }
 
-    fn primary(&mut self) -> Expr {
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
+	let mut arguments = vec![];
+	let mut named: Vec<(Token, Expr)> = vec![];
+	if !self.check(&RightParen) {
+	    loop {
+		if arguments.len() + named.len() >= 255 {
+		    self.error(&self.peek(), "Cannot have more than 255 arguments.");
+		}
+		if self.check(&Identifier) && self.check_next(&Colon) {
+		    let name = self.advance();
+		    self.advance();
+		    if named.iter().any(|(other, _)| other.lexeme == name.lexeme) {
+			self.error(&name, "Argument passed by name twice.");
+		    }
+		    named.push((name, self.element()?));
+		} else {
+		    if !named.is_empty() {
+			self.error(&self.peek(), "Positional argument can't follow a named one.");
+		    }
+		    arguments.push(self.element()?);
+		}
+		if !self.mtch(vec![TokenType::Comma]) { break; }
+	    }
+	}
+
+	let paren = self.consume(&RightParen, "Expect ')' after arguments.")?;
+	Ok(Call { callee: Box::new(callee), paren, arguments, named })
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
 	let mut advance = true;
 	let res = match self.peek().type_ {
 	    False => Literal { value: Value::False },
@@ -161,39 +824,156 @@ impl Parser {
 	    Number { literal } => Literal { value: Value::Number(literal) },
 	    STRING { literal } => Literal { value: Value::Strng(literal) },
 	    // not quite a transliteration, because we're rust match, and we're advancing after
-	    Identifier => Variable { name: self.peek() },
+	    Identifier => Variable { name: self.peek(), depth: Cell::new(None) },
+	    TokenType::This => Expr::This { keyword: self.peek(), depth: Cell::new(None) },
+	    TokenType::Super => {
+		let keyword = self.advance();
+		self.consume(&Dot, "Expect '.' after 'super'.")?;
+		let method = self.consume(&Identifier, "Expect superclass method name.")?;
+		advance = false;
+		Expr::Super { keyword, method, depth: Cell::new(None) }
+	    }
+	    Fun => {
+		self.advance();
+		advance = false;
+		Lambda { declaration: self.function_body(None, "lambda")? }
+	    }
+	    LeftParen if self.starts_arrow() => {
+		advance = false;
+		Lambda { declaration: self.arrow()? }
+	    }
 	    LeftParen => {
 		// move past the left paren
 		self.advance();
 		// consume the expression
-		let expr = self.expression();
+		let expr = self.expression()?;
 		// eat the right paren
-		self.consume(&RightParen, "Expect ')' after expression.");
+		self.consume(&RightParen, "Expect ')' after expression.")?;
 		// don't advance past the right paren
 		advance = false;
 		Grouping { expression: Box::new(expr) }
 
 	    }
-	    _ => {
-		println!("failing token {:?}", self.peek());
-		panic!("failed in primary on not matching")
+	    LeftBrace => {
+		let brace = self.advance();
+		let mut entries = vec![];
+		// allows a trailing comma, like lists
+		while !self.check(&RightBrace) {
+		    let key = self.element()?;
+		    self.consume(&Colon, "Expect ':' after map key.")?;
+		    let value = self.element()?;
+		    entries.push((key, value));
+		    if !self.mtch(vec![TokenType::Comma]) { break; }
+		}
+		self.consume(&RightBrace, "Expect '}' after map entries.")?;
+		advance = false;
+		Map { brace, entries }
 	    }
+	    LeftBracket => {
+		self.advance();
+		let mut elements = vec![];
+		// allows a trailing comma
+		while !self.check(&RightBracket) {
+		    elements.push(self.element()?);
+		    if !self.mtch(vec![TokenType::Comma]) { break; }
+		}
+		self.consume(&RightBracket, "Expect ']' after list elements.")?;
+		advance = false;
+		List { elements }
+	    }
+	    _ => return Err(self.error(&self.peek(), "Expect expression."))
 	};
 	// hacky skip for grouping
 	if advance { self.advance(); }
-	res
+	Ok(res)
     }
 
-    // currently does not do the erroring that the Java version does
-    fn consume(&mut self, type_: &TokenType, message: &str) -> Option<Token> {
-	if self.check(type_) { 
-	    Some(self.advance())
+    // (a, b) => a + b, or (a, b) => { ... }
+    // the expression form is the same as a body of just `return a + b;`
+    fn arrow(&mut self) -> ParseResult<Rc<FunctionDecl>> {
+	self.advance();
+	let (params, rest) = self.parameters()?;
+	let arrow = self.consume(&Arrow, "Expect '=>' after parameters.")?;
+
+	let body = if self.check(&LeftBrace) && !self.starts_map() {
+	    self.advance();
+	    self.function_block()?
 	} else {
-	    println!("{}", message);
-	    None
+	    // an element, so a lambda can be one of several call arguments
+	    let value = self.element()?;
+	    vec![Stmt::Return { keyword: arrow, value: Some(Box::new(value)) }]
+	};
+	Ok(Rc::new(FunctionDecl { name: None, params, rest, body }))
+    }
+
+    // a '(' starts an arrow lambda if the ')' it's matched with is followed by '=>'
+    // defaults can be any expression, so this skips to the ')' rather than parsing
+    fn starts_arrow(&self) -> bool {
+	let mut open = 0;
+	for (i, token) in self.tokens.iter().enumerate().skip(self.current) {
+	    match token.type_ {
+		LeftParen | LeftBracket | LeftBrace => open += 1,
+		RightParen | RightBracket | RightBrace => {
+		    open -= 1;
+		    if open == 0 {
+			return self.tokens.get(i + 1).map(|t| &t.type_) == Some(&Arrow);
+		    }
+		},
+		Eof => return false,
+		_ => {}
+	    }
+	}
+	false
+    }
+
+    // one level deeper into the recursion; callers undo it with self.depth -= 1
+    fn nest(&mut self, message: &str) -> ParseResult<()> {
+	if self.depth >= self.max_depth {
+	    return Err(self.error(&self.peek(), message));
+	}
+	self.depth += 1;
+	Ok(())
+    }
+
+    fn consume(&mut self, type_: &TokenType, message: &str) -> ParseResult<Token> {
+	if self.check(type_) {
+	    Ok(self.advance())
+	} else {
+	    Err(self.error(&self.peek(), message))
+	}
+    }
+
+    // Parser.error: jlox/Parser.java
+    fn error(&mut self, token: &Token, message: &str) -> ParseError {
+	crate::lox::token_error(token, message);
+	self.had_error = true;
+	ParseError
+    }
+
+    // skip to what's probably the start of the next statement
+    fn synchronize(&mut self) {
+	self.advance();
+
+	while !self.is_at_end() {
+	    if self.previous().type_ == Semicolon { return; }
+
+	    match self.peek().type_ {
+		Class | Trait | Import | Fun | Var | Const | For | If | While | Print | Return | Throw | Try | Match => return,
+		_ => {}
+	    }
+
+	    self.advance();
 	}
     }
 
+    // a '{' starting a statement is a block, unless it's clearly a map:
+    // a string or number key followed by ':', which a block can't start with
+    fn starts_map(&self) -> bool {
+	let key = self.tokens.get(self.current + 1).map(|t| &t.type_);
+	let colon = self.tokens.get(self.current + 2).map(|t| &t.type_);
+	matches!((key, colon), (Some(STRING { .. }), Some(Colon)) | (Some(Number { .. }), Some(Colon)))
+    }
+
     fn mtch(&mut self, types: Vec<TokenType>) -> bool {
 	for type_ in types.iter() {
 	    if self.check(type_) {
@@ -210,6 +990,13 @@ impl Parser {
 	result
     }
 
+    fn check_next(&self, token_type: &TokenType) -> bool {
+	match self.tokens.get(self.current + 1) {
+	    Some(token) => &token.type_ == token_type,
+	    None => false
+	}
+    }
+
     fn previous(&self) -> Token {
 	self.tokens[self.current - 1].clone()
     }
@@ -0,0 +1,507 @@
+// maps to jlox-java file jlox/Resolver.java
+// a pass between the parser and the interpreter. like jlox's, it works out which
+// scope each variable, `this` and `super` refers to, so a closure keeps seeing the
+// variable it was declared next to. it also reports what it can see is wrong
+// without running anything: assigning to a constant, and in strict mode,
+// undeclared globals, redefined globals and unused locals.
+// its scopes have to match the interpreter's environments one for one
+use std::rc::Rc;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use crate::expr::{self, Expr, Value};
+use crate::stmt::{self, Stmt, FunctionDecl, MatchArm, Pattern, Method, MethodKind, Imported};
+use crate::token::Token;
+
+// a script that starts with this statement is resolved in strict mode, like javascript
+const STRICT_PRAGMA: &str = "use strict";
+
+// Resolver.ClassType: jlox/Resolver.java L24
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+    // super is allowed, it's only known at runtime whether the classes using it have a superclass
+    Trait,
+}
+
+struct Binding {
+    name: Token,
+    constant: bool,
+    used: bool,
+    // params and catch variables are often unused on purpose, so they're never reported
+    report_unused: bool,
+}
+
+pub struct Resolver {
+    // innermost last, the first one is the globals
+    scopes: Vec<HashMap<String, Binding>>,
+    strict: bool,
+    // strict mode: globals from before this script, natives and earlier REPL lines
+    earlier_globals: HashSet<String>,
+    // strict mode: every global this script declares at the top level, which a
+    // function body can use before the declaration
+    script_globals: HashSet<String>,
+    function_depth: usize,
+    // whether we're in a class's init, where return can't have a value
+    initializer: bool,
+    class: ClassType,
+    pub had_error: bool,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: vec![HashMap::new()],
+            strict: false,
+            earlier_globals: HashSet::new(),
+            script_globals: HashSet::new(),
+            function_depth: 0,
+            initializer: false,
+            class: ClassType::None,
+            had_error: false,
+        }
+    }
+
+    // globals are the names already defined before the script runs
+    pub fn set_strict(&mut self, globals: Vec<String>) {
+        self.strict = true;
+        self.earlier_globals = globals.into_iter().collect();
+    }
+
+    // whether the script opts in with "use strict"; as its first statement
+    pub fn has_strict_pragma(stmts: &[Stmt]) -> bool {
+        match stmts.first() {
+            Some(Stmt::Expression { expr }) => match &**expr {
+                Expr::Literal { value: Value::Strng(s) } => s == STRICT_PRAGMA,
+                _ => false
+            },
+            _ => false
+        }
+    }
+
+    // the entry point, for a whole script
+    pub fn resolve_script(&mut self, stmts: &[Stmt]) {
+        if self.strict {
+            for stmt in stmts {
+                let name = match stmt {
+                    Stmt::Var { name, .. } | Stmt::Class { name, .. } | Stmt::Trait { name, .. } => name,
+                    Stmt::Function { declaration } => match &declaration.name {
+                        Some(name) => name,
+                        None => continue,
+                    },
+                    Stmt::Import { imported: Imported::Names(names), .. } => {
+                        self.script_globals.extend(names.iter().map(|name| name.lexeme.clone()));
+                        continue;
+                    },
+                    Stmt::Import { imported: Imported::Module(name), .. } => name,
+                    _ => continue,
+                };
+                self.script_globals.insert(name.lexeme.clone());
+            }
+        }
+        self.resolve(stmts);
+    }
+
+    fn resolve(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            stmt.accept(self);
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            if !self.strict { return; }
+            let mut unused: Vec<&Binding> = scope.values()
+                .filter(|binding| !binding.used && binding.report_unused)
+                .collect();
+            // the same order every time, not the hashmap's
+            unused.sort_by_key(|binding| binding.name.line);
+            for binding in unused {
+                self.error(&binding.name, &format!("Local variable '{}' is never used.", binding.name.lexeme));
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token, constant: bool) {
+        self.bind(name, constant, true);
+    }
+
+    // a param or catch variable, which doesn't have to be used
+    fn declare_param(&mut self, name: &Token) {
+        self.bind(name, false, false);
+    }
+
+    fn bind(&mut self, name: &Token, constant: bool, report_unused: bool) {
+        let global = self.scopes.len() == 1;
+        // any declaration replacing a constant in the same scope, not just var
+        let redeclared = self.scopes.last().and_then(|scope| scope.get(&name.lexeme));
+        if redeclared.is_some_and(|binding| binding.constant) {
+            self.error(name, &format!("Cannot assign to constant '{}'.", name.lexeme));
+        }
+        let defined = self.scopes[0].contains_key(&name.lexeme) || self.earlier_globals.contains(&name.lexeme);
+        if self.strict && global && defined {
+            self.error(name, &format!("Global '{}' is already defined.", name.lexeme));
+        }
+        // a name starting with _ is unused on purpose
+        let report_unused = report_unused && !global && !name.lexeme.starts_with('_');
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), Binding { name: name.clone(), constant, used: false, report_unused });
+        }
+    }
+
+    // `this` or `super`, which the interpreter defines in an environment of their own
+    fn begin_implicit_scope(&mut self, near: &Token, name: &str) {
+        self.begin_scope();
+        self.declare_param(&Token { lexeme: name.to_string(), ..near.clone() });
+    }
+
+    // Resolver.resolveLocal: jlox/Resolver.java L68
+    // a name that isn't in any scope is a global, maybe one declared further down,
+    // so it gets the global scope's distance too
+    fn resolve_local(&self, name: &str, depth: &Cell<Option<usize>>) {
+        let globals = self.scopes.len() - 1;
+        let distance = self.scopes.iter().rev().position(|scope| scope.contains_key(name));
+        depth.set(Some(distance.unwrap_or(globals)));
+    }
+
+    fn lookup(&mut self, name: &Token) -> Option<&mut Binding> {
+        self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(&name.lexeme))
+    }
+
+    // strict mode: a name that isn't declared in an enclosing scope has to be a
+    // global that was already there, or, inside a function, one the script declares
+    // anywhere at the top level. top level code has to come after the declaration
+    fn check_declared(&mut self, name: &Token) {
+        if !self.strict || self.lookup(name).is_some() { return; }
+        let declared = self.earlier_globals.contains(&name.lexeme)
+            || (self.function_depth > 0 && self.script_globals.contains(&name.lexeme));
+        if !declared {
+            self.error(name, &format!("Undefined variable '{}'.", name.lexeme));
+        }
+    }
+
+    // the innermost declaration of name decides. one we haven't seen, like a
+    // global declared further down, is left to the check in Environment::assign
+    fn check_assign(&mut self, name: &Token) {
+        self.check_declared(name);
+        if self.lookup(name).is_some_and(|binding| binding.constant) {
+            self.error(name, &format!("Cannot assign to constant '{}'.", name.lexeme));
+        }
+    }
+
+    // Resolver.resolveFunction: jlox/Resolver.java L79
+    // params, defaults and the body share a scope, like they share an Environment
+    fn resolve_function(&mut self, declaration: &FunctionDecl, initializer: bool) {
+        let enclosing_initializer = self.initializer;
+        self.initializer = initializer;
+        self.begin_scope();
+        self.function_depth += 1;
+        for param in declaration.params.iter() {
+            if let Some(default) = &param.default {
+                self.resolve_expr(default);
+            }
+            self.declare_param(&param.name);
+        }
+        if let Some(rest) = &declaration.rest {
+            self.declare_param(rest);
+        }
+        self.resolve(&declaration.body);
+        self.function_depth -= 1;
+        self.end_scope();
+        self.initializer = enclosing_initializer;
+    }
+
+    fn resolve_methods(&mut self, methods: &[Method]) {
+        for method in methods.iter() {
+            let initializer = method.kind == MethodKind::Method
+                && method.declaration.name.as_ref().is_some_and(|name| name.lexeme == "init");
+            self.resolve_function(&method.declaration, initializer);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        crate::lox::token_error(token, message);
+        self.had_error = true;
+    }
+}
+
+impl stmt::Visitor<()> for Resolver {
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) {
+        self.begin_scope();
+        self.resolve(stmts);
+        self.end_scope();
+    }
+
+    fn visit_break(&mut self, _keyword: &Token) {}
+
+    // Resolver.visitClassStmt: jlox/Resolver.java L103
+    // methods are inside a scope for `super` when there's a superclass, then one for `this`
+    fn visit_class(&mut self, name: &Token, superclass: &Option<Box<Expr>>, traits: &[Expr], methods: &[Method]) {
+        let enclosing_class = self.class;
+        self.class = ClassType::Class;
+        self.declare(name, false);
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable { name: superclass_name, .. } = &**superclass {
+                if superclass_name.lexeme == name.lexeme {
+                    self.error(superclass_name, "A class can't inherit from itself.");
+                }
+            }
+            self.class = ClassType::Subclass;
+            self.resolve_expr(superclass);
+        }
+        for mixin in traits.iter() {
+            self.resolve_expr(mixin);
+        }
+
+        if superclass.is_some() {
+            self.begin_implicit_scope(name, "super");
+        }
+        self.begin_implicit_scope(name, "this");
+        self.resolve_methods(methods);
+        self.end_scope();
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        self.class = enclosing_class;
+    }
+
+    // a trait's methods always get a `super` scope, since the class they're mixed
+    // into may or may not have a superclass to put in it
+    fn visit_trait(&mut self, name: &Token, methods: &[Method]) {
+        let enclosing_class = self.class;
+        self.class = ClassType::Trait;
+        self.declare(name, false);
+        self.begin_implicit_scope(name, "super");
+        self.begin_implicit_scope(name, "this");
+        self.resolve_methods(methods);
+        self.end_scope();
+        self.end_scope();
+        self.class = enclosing_class;
+    }
+
+    fn visit_continue(&mut self, _keyword: &Token) {}
+
+    fn visit_expression(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_for_in(&mut self, name: &Token, _keyword: &Token, iterable: &Expr, body: &Stmt) {
+        self.resolve_expr(iterable);
+        self.begin_scope();
+        self.declare(name, false);
+        body.accept(self);
+        self.end_scope();
+    }
+
+    // declared first, so the function can call itself
+    fn visit_function(&mut self, declaration: &Rc<FunctionDecl>) {
+        if let Some(name) = &declaration.name {
+            self.declare(name, false);
+        }
+        self.resolve_function(declaration, false);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
+        self.resolve_expr(condition);
+        then_branch.accept(self);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_import(&mut self, _keyword: &Token, _path: &str, imported: &Imported) {
+        match imported {
+            Imported::Module(name) => self.declare(name, false),
+            Imported::Names(names) => {
+                for name in names.iter() {
+                    self.declare(name, false);
+                }
+            },
+        }
+    }
+
+    // each arm is its own scope, for its binding
+    fn visit_match(&mut self, _keyword: &Token, value: &Expr, arms: &[MatchArm]) {
+        self.resolve_expr(value);
+        for arm in arms.iter() {
+            self.begin_scope();
+            for pattern in arm.patterns.iter() {
+                if let Pattern::Binding(name) = pattern {
+                    self.declare_param(name);
+                }
+            }
+            if let Some(guard) = &arm.guard {
+                self.resolve_expr(guard);
+            }
+            arm.body.accept(self);
+            self.end_scope();
+        }
+    }
+
+    fn visit_print(&mut self, _keyword: &Token, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: &Option<Box<Expr>>) {
+        if let Some(value) = value {
+            if self.initializer {
+                self.error(keyword, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_throw(&mut self, _keyword: &Token, value: &Expr) {
+        self.resolve_expr(value);
+    }
+
+    fn visit_try(&mut self, body: &[Stmt], catch: &Option<(Token, Vec<Stmt>)>, finally: &Option<Vec<Stmt>>) {
+        self.begin_scope();
+        self.resolve(body);
+        self.end_scope();
+        if let Some((name, handler)) = catch {
+            self.begin_scope();
+            self.declare_param(name);
+            self.resolve(handler);
+            self.end_scope();
+        }
+        if let Some(finally) = finally {
+            self.visit_block(finally);
+        }
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: &Expr, constant: bool) {
+        self.resolve_expr(initializer);
+        self.declare(name, constant);
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Box<Expr>>) {
+        self.resolve_expr(condition);
+        body.accept(self);
+        if let Some(increment) = increment {
+            self.resolve_expr(increment);
+        }
+    }
+}
+
+impl expr::Visitor<()> for Resolver {
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, depth: &Cell<Option<usize>>) {
+        self.resolve_expr(value);
+        self.check_assign(name);
+        self.resolve_local(&name.lexeme, depth);
+    }
+
+    fn visit_binary(&mut self, left: &Expr, _operator: &Token, right: &Expr) {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, arguments: &[Expr], named: &[(Token, Expr)]) {
+        self.resolve_expr(callee);
+        for argument in arguments.iter() {
+            self.resolve_expr(argument);
+        }
+        for (_, argument) in named.iter() {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn visit_comma(&mut self, left: &Expr, right: &Expr) {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+    }
+
+    fn visit_compound_assign(&mut self, target: &Expr, _operator: &Token, value: &Expr, _postfix: bool) {
+        self.resolve_expr(target);
+        self.resolve_expr(value);
+        if let Expr::Variable { name, .. } = target {
+            self.check_assign(name);
+        }
+    }
+
+    fn visit_get(&mut self, object: &Expr, _name: &Token) {
+        self.resolve_expr(object);
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) {
+        self.resolve_expr(expression);
+    }
+
+    fn visit_index(&mut self, object: &Expr, _bracket: &Token, index: &Expr) {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+    }
+
+    fn visit_list(&mut self, elements: &[Expr]) {
+        for element in elements.iter() {
+            self.resolve_expr(element);
+        }
+    }
+
+    fn visit_lambda(&mut self, declaration: &Rc<FunctionDecl>) {
+        self.resolve_function(declaration, false);
+    }
+
+    fn visit_literal(&self, _value: &Value) {}
+
+    fn visit_map(&mut self, _brace: &Token, entries: &[(Expr, Expr)]) {
+        for (key, value) in entries.iter() {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_set(&mut self, object: &Expr, _name: &Token, value: &Expr) {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+    }
+
+    fn visit_set_index(&mut self, object: &Expr, _bracket: &Token, index: &Expr, value: &Expr) {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+        self.resolve_expr(value);
+    }
+
+    fn visit_ternary(&mut self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) {
+        self.resolve_expr(condition);
+        self.resolve_expr(then_branch);
+        self.resolve_expr(else_branch);
+    }
+
+    fn visit_super(&mut self, keyword: &Token, _method: &Token, depth: &Cell<Option<usize>>) {
+        match self.class {
+            ClassType::None => self.error(keyword, "Can't use 'super' outside of a class."),
+            ClassType::Class => self.error(keyword, "Can't use 'super' in a class with no superclass."),
+            ClassType::Subclass | ClassType::Trait => self.resolve_local("super", depth),
+        }
+    }
+
+    fn visit_this(&mut self, keyword: &Token, depth: &Cell<Option<usize>>) {
+        if self.class == ClassType::None {
+            self.error(keyword, "Can't use 'this' outside of a class.");
+        }
+        self.resolve_local("this", depth);
+    }
+
+    fn visit_unary(&mut self, _operator: &Token, right: &Expr) {
+        self.resolve_expr(right);
+    }
+
+    fn visit_variable(&mut self, name: &Token, depth: &Cell<Option<usize>>) {
+        self.check_declared(name);
+        if let Some(binding) = self.lookup(name) {
+            binding.used = true;
+        }
+        self.resolve_local(&name.lexeme, depth);
+    }
+}
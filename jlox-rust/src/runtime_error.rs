@@ -0,0 +1,90 @@
+// maps to jlox-java file jlox/RuntimeError.java
+// Java throws these as exceptions; here every evaluate/execute returns a Result
+// and `?` does the unwinding
+use crate::token::Token;
+use crate::expr::Value;
+
+// lets an embedder tell a broken script apart from one that ran out of budget
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    // type errors, undefined variables, bad calls
+    Runtime,
+    // a Lox `throw`
+    Thrown,
+    StepLimit,
+    CallDepth,
+    // recursion went deeper than the interpreter's max_depth
+    StackOverflow,
+    Timeout,
+    Cancelled,
+    // the script called exit(code)
+    Exit(i32),
+}
+
+// a call in progress: who was called, and the line it was called from
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub function: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    // None when there's no token to blame, e.g. the deadline passing between statements
+    pub line: Option<usize>,
+    // the interpreter's call stack when the error happened, outermost first
+    // empty for errors in the top level script
+    pub trace: Vec<CallFrame>,
+    // what was thrown, for ErrorKind::Thrown
+    pub value: Option<Value>,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, message: &str) -> RuntimeError {
+        RuntimeError {
+            kind: ErrorKind::Runtime,
+            message: message.to_string(),
+            line: Some(token.line),
+            trace: vec![],
+            value: None,
+        }
+    }
+
+    // `throw value`, which is only an error if nothing catches it
+    pub fn thrown(token: &Token, value: Value) -> RuntimeError {
+        RuntimeError {
+            kind: ErrorKind::Thrown,
+            message: format!("Uncaught exception: {}", value),
+            line: Some(token.line),
+            trace: vec![],
+            value: Some(value),
+        }
+    }
+
+    // runtime errors and throws can be caught, running out of budget or stack can't
+    pub fn is_catchable(&self) -> bool {
+        self.kind == ErrorKind::Runtime || self.kind == ErrorKind::Thrown
+    }
+
+    pub fn exit(code: i32) -> RuntimeError {
+        RuntimeError {
+            kind: ErrorKind::Exit(code),
+            message: format!("Exited with code {}.", code),
+            line: None,
+            trace: vec![],
+            value: None,
+        }
+    }
+
+    pub fn limit(kind: ErrorKind, line: Option<usize>, message: &str) -> RuntimeError {
+        RuntimeError {
+            kind,
+            message: message.to_string(),
+            line,
+            trace: vec![],
+            value: None,
+        }
+    }
+}
@@ -9,11 +9,16 @@ use crate::token_type::TokenType::*;
 // class definition, final variables
 #[derive(Debug)]
 pub struct Scanner {
-    source: String,
+    // chars rather than a String, so indexing is by character and not by byte
+    source: Vec<char>,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    // for each open paren, whether it's the head of an if, while, for, catch or fun
+    parens: Vec<bool>,
+    // whether the last `)` closed one of those heads
+    closed_head: bool,
 }
 
 // match instead of a hashmap, since rust is picky about hashmaps...
@@ -23,21 +28,33 @@ pub struct Scanner {
 fn keyword_get(text: &str) -> Option<TokenType> {
     match text {
         "and" => Some(And),
+        "break" => Some(Break),
+        "catch" => Some(Catch),
         "class" => Some(Class),
+        "const" => Some(Const),
+        "continue" => Some(Continue),
         "else" => Some(Else),
         "false" => Some(False),
+        "finally" => Some(Finally),
         "for" => Some(For),
         "fun" => Some(Fun),
         "if" => Some(If),
+        "import" => Some(Import),
+        "in" => Some(In),
+        "match" => Some(Match),
         "nil" => Some(Nil),
         "or" => Some(Or),
         "print" => Some(Print),
         "return" => Some(Return),
         "super" => Some(Super),
         "this" => Some(This),
+        "throw" => Some(Throw),
+        "trait" => Some(Trait),
         "true" => Some(True),
+        "try" => Some(Try),
         "var" => Some(Var),
         "while" => Some(While),
+        "with" => Some(With),
         _ => None
     }
 }
@@ -46,11 +63,13 @@ impl Scanner {
     // Scanner.Scanner L38
     pub fn new(source: String) -> Scanner {
         Scanner {
-            source,
+            source: source.chars().collect(),
             tokens: Vec::new(),
             start: 0,
             current: 0,
-            line: 1
+            line: 1,
+            parens: Vec::new(),
+            closed_head: false,
         }
     }
 
@@ -72,23 +91,65 @@ impl Scanner {
     fn scan_token(&mut self) {
         let c: char = self.advance().unwrap();
         match c {
-            '(' => self.add_token(LeftParen),
-            ')' => self.add_token(RightParen),
+            '(' => {
+                let head = self.opens_head();
+                self.parens.push(head);
+                self.add_token(LeftParen)
+            },
+            ')' => {
+                self.closed_head = self.parens.pop().unwrap_or(false);
+                self.add_token(RightParen)
+            },
             '{' => self.add_token(LeftBrace),
             '}' => self.add_token(RightBrace),
+            '[' => self.add_token(LeftBracket),
+            ']' => self.add_token(RightBracket),
             ',' => self.add_token(Comma),
-            '.' => self.add_token(Dot),
-            '-' => self.add_token(Minus),
-            '+' => self.add_token(Plus),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == Some('.') {
+                    self.advance();
+                    self.advance();
+                    self.add_token(DotDotDot)
+                } else {
+                    self.add_token(Dot)
+                }
+            },
+            '-' => {
+                if self.match_('-') { self.add_token(MinusMinus) }
+                else if self.match_('=') { self.add_token(MinusEqual) }
+                else { self.add_token(Minus) }
+            },
+            '+' => {
+                if self.match_('+') { self.add_token(PlusPlus) }
+                else if self.match_('=') { self.add_token(PlusEqual) }
+                else { self.add_token(Plus) }
+            },
             ';' => self.add_token(Semicolon),
-            '*' => self.add_token(Star),
+            ':' => self.add_token(Colon),
+            '?' => self.add_token(Question),
+            '|' => self.add_token(Pipe),
+            '*' => {
+                if self.match_('*') { self.add_token(StarStar) }
+                else if self.match_('=') { self.add_token(StarEqual) }
+                else { self.add_token(Star) }
+            },
+            '%' => if self.match_('=') { self.add_token(PercentEqual) } else { self.add_token(Percent) },
             '!' => if self.match_('=') { self.add_token(BangEqual) } else { self.add_token(Bang) },
-            '=' => if self.match_('=') { self.add_token(EqualEqual) } else { self.add_token(Equal) },
+            '=' => {
+                if self.match_('=') { self.add_token(EqualEqual) }
+                else if self.match_('>') { self.add_token(Arrow) }
+                else { self.add_token(Equal) }
+            },
             '<' => if self.match_('=') { self.add_token(LessEqual) } else { self.add_token(Less) },
             '>' => if self.match_('=') { self.add_token(GreaterEqual) } else { self.add_token(Greater) },
-            '/' => { 
-                if self.match_('/') { 
+            '/' => {
+                if self.peek() == '/' && self.after_operand() {
+                    self.advance();
+                    self.add_token(SlashSlash)
+                } else if self.match_('/') {
                     while self.peek() != '\n' && !self.is_at_end() { self.advance(); };
+                } else if self.match_('=') {
+                    self.add_token(SlashEqual)
                 } else {
                     self.add_token(Slash)
                 }
@@ -109,9 +170,34 @@ impl Scanner {
         }
     }
 
+    // `//` is floor division straight after an operand on the same line, as in `7 // 2`,
+    // and a comment anywhere else: at the start of a line, or after `;`, `,`, `{` and
+    // the like. a comment after an operand needs a `;` or a line break before it
+    fn after_operand(&self) -> bool {
+        match self.tokens.last() {
+            Some(token) if token.line == self.line => match token.type_ {
+                Number { .. } | STRING { .. } | Identifier | True | False | Nil | This | RightBracket => true,
+                // `if (x) // ...` is still a comment
+                RightParen => !self.closed_head,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    // is the `(` being scanned the head of an if, while, for, catch or fun, rather than
+    // a call or a grouping, so what comes after its `)` isn't an operand
+    fn opens_head(&self) -> bool {
+        let mut previous = self.tokens.iter().rev().map(|token| &token.type_);
+        match previous.next() {
+            Some(If) | Some(While) | Some(For) | Some(Catch) | Some(Fun) => true,
+            Some(Identifier) => previous.next() == Some(&Fun),
+            _ => false,
+        }
+    }
+
     fn cur(&self) -> Option<char> {
-        // fyi - this is O(n), and supports utf8
-        self.source.chars().nth(self.current)
+        self.source.get(self.current).copied()
     }
 
     fn peek(&self) -> char {
@@ -123,11 +209,7 @@ impl Scanner {
     }
 
     fn peek_next(&self) -> Option<char> {
-        if self.current + 1 > self.source.len() {
-            None
-        } else {
-            self.source.chars().nth(self.current + 1)
-        }
+        self.source.get(self.current + 1).copied()
     }
 
     // match is a keyword
@@ -140,11 +222,11 @@ impl Scanner {
 
     fn advance(&mut self) -> Option<char> {
         self.current += 1;
-        self.source.chars().nth(self.current - 1)
+        self.source.get(self.current - 1).copied()
     }
 
     fn current_substring(&self) -> String {
-        self.source[self.start..self.current].to_string()
+        self.source[self.start..self.current].iter().collect()
     }
 
     fn add_token(&mut self, type_: TokenType) {
@@ -185,7 +267,7 @@ impl Scanner {
         }
 
         self.advance();
-        let literal: String = self.source[self.start + 1..self.current - 1].to_string();
+        let literal: String = self.source[self.start + 1..self.current - 1].iter().collect();
         self.add_token(STRING { literal })
     }
 
@@ -1,20 +1,121 @@
 // in theory, would be generated by bin/generate_ast.rs
 // in parallel to expr.rs
-use crate::expr::Expr;
+use std::rc::Rc;
+use crate::expr::{Expr, Value};
 use crate::token::Token;
 
+#[derive(Debug)]
 pub enum Stmt {
     Block { stmts: Vec<Stmt> },
+    Break { keyword: Token },
+    // class name < superclass with trait, trait { methods }
+    // the superclass and traits are Variables
+    Class { name: Token, superclass: Option<Box<Expr>>, traits: Vec<Expr>, methods: Vec<Method> },
+    Continue { keyword: Token },
     Expression { expr: Box<Expr> },
-    Print { expr: Box<Expr> },
-    Var { name: Token, initializer: Box<Expr> }
+    Function { declaration: Rc<FunctionDecl> },
+    If { condition: Box<Expr>, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>> },
+    // import "path" as name; or from "path" import a, b;
+    Import { keyword: Token, path: String, imported: Imported },
+    // match value { pattern | pattern if guard => body, ... }, runs the first arm that matches
+    Match { keyword: Token, value: Box<Expr>, arms: Vec<MatchArm> },
+    // the keyword is for the line number when __str__ goes wrong
+    Print { keyword: Token, expr: Box<Expr> },
+    Return { keyword: Token, value: Option<Box<Expr>> },
+    Throw { keyword: Token, value: Box<Expr> },
+    // trait name { methods }, methods for classes to mix in
+    Trait { name: Token, methods: Vec<Method> },
+    // try {} catch (name) {} finally {}, with at least one of catch and finally
+    Try { body: Vec<Stmt>, catch: Option<(Token, Vec<Stmt>)>, finally: Option<Vec<Stmt>> },
+    // for (var name in iterable) body, with a new environment for each item
+    ForIn { name: Token, keyword: Token, iterable: Box<Expr>, body: Box<Stmt> },
+    // `const` when constant, which can't be assigned to after this
+    Var { name: Token, initializer: Box<Expr>, constant: bool },
+    // for loops desugar to a while with an increment, which runs after the body
+    // even when the body `continue`s, so it can't just be the last statement of the body
+    While { condition: Box<Expr>, body: Box<Stmt>, increment: Option<Box<Expr>> },
+}
+
+// shared by `fun name() {}` statements and lambda expressions,
+// and by every LoxFunction made from them, so the body is never copied
+#[derive(Debug)]
+pub struct FunctionDecl {
+    // None for a lambda
+    pub name: Option<Token>,
+    pub params: Vec<Param>,
+    // ...name, the arguments after the params as a list
+    pub rest: Option<Token>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+pub struct Method {
+    pub kind: MethodKind,
+    pub declaration: Rc<FunctionDecl>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MethodKind {
+    Method,
+    // class name() {}, called on the class
+    Static,
+    // name {}, called when the property is read, without parens
+    Getter,
+    // set name(value) {}, called when the property is assigned
+    Setter,
+}
+
+#[derive(Debug)]
+pub enum Imported {
+    // as name, the whole module
+    Module(Token),
+    // import a, b: just those declarations
+    Names(Vec<Token>),
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: Token,
+    // evaluated at each call that leaves it out, after the params before it are bound
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    // alternatives, any one of them matching is enough
+    pub patterns: Vec<Pattern>,
+    pub guard: Option<Expr>,
+    pub body: Box<Stmt>,
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    // compared with ==
+    Literal(Value),
+    // _, matches anything
+    Wildcard,
+    // a name, matches anything and binds it for the guard and body
+    Binding(Token),
 }
 
 pub trait Visitor<T> {
     fn visit_block(&mut self, stmts: &Vec<Stmt>) -> T;
+    fn visit_break(&mut self, keyword: &Token) -> T;
+    fn visit_class(&mut self, name: &Token, superclass: &Option<Box<Expr>>, traits: &[Expr], methods: &[Method]) -> T;
+    fn visit_continue(&mut self, keyword: &Token) -> T;
     fn visit_expression(&mut self, expr: &Expr) -> T;
-    fn visit_print(&mut self, expr: &Expr) -> T;
-    fn visit_var(&mut self, name: &Token, initializer: &Expr) -> T;
+    fn visit_for_in(&mut self, name: &Token, keyword: &Token, iterable: &Expr, body: &Stmt) -> T;
+    fn visit_function(&mut self, declaration: &Rc<FunctionDecl>) -> T;
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) -> T;
+    fn visit_import(&mut self, keyword: &Token, path: &str, imported: &Imported) -> T;
+    fn visit_match(&mut self, keyword: &Token, value: &Expr, arms: &[MatchArm]) -> T;
+    fn visit_print(&mut self, keyword: &Token, expr: &Expr) -> T;
+    fn visit_return(&mut self, keyword: &Token, value: &Option<Box<Expr>>) -> T;
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> T;
+    fn visit_trait(&mut self, name: &Token, methods: &[Method]) -> T;
+    fn visit_try(&mut self, body: &[Stmt], catch: &Option<(Token, Vec<Stmt>)>, finally: &Option<Vec<Stmt>>) -> T;
+    fn visit_var(&mut self, name: &Token, initializer: &Expr, constant: bool) -> T;
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Box<Expr>>) -> T;
 }
 
 impl Stmt {
@@ -22,9 +123,22 @@ impl Stmt {
 	use crate::stmt::Stmt::*;
 	match self {
 	    Block { stmts } => visitor.visit_block(stmts),
+	    Break { keyword } => visitor.visit_break(keyword),
+	    Class { name, superclass, traits, methods } => visitor.visit_class(name, superclass, traits, methods),
+	    Continue { keyword } => visitor.visit_continue(keyword),
 	    Expression { expr } => visitor.visit_expression(expr),
-	    Print { expr } => visitor.visit_print(expr),
-	    Var { name, initializer } => visitor.visit_var(name, initializer),
+	    ForIn { name, keyword, iterable, body } => visitor.visit_for_in(name, keyword, iterable, body),
+	    Function { declaration } => visitor.visit_function(declaration),
+	    If { condition, then_branch, else_branch } => visitor.visit_if(condition, then_branch, else_branch),
+	    Import { keyword, path, imported } => visitor.visit_import(keyword, path, imported),
+	    Match { keyword, value, arms } => visitor.visit_match(keyword, value, arms),
+	    Print { keyword, expr } => visitor.visit_print(keyword, expr),
+	    Return { keyword, value } => visitor.visit_return(keyword, value),
+	    Throw { keyword, value } => visitor.visit_throw(keyword, value),
+	    Trait { name, methods } => visitor.visit_trait(name, methods),
+	    Try { body, catch, finally } => visitor.visit_try(body, catch, finally),
+	    Var { name, initializer, constant } => visitor.visit_var(name, initializer, *constant),
+	    While { condition, body, increment } => visitor.visit_while(condition, body, increment),
 	}
     }
 }
@@ -0,0 +1,103 @@
+// string natives, defined as globals
+// indexes count chars (unicode scalar values), not bytes, so slicing can't
+// land in the middle of a multi-byte character
+use crate::expr::Value;
+use crate::environment::Environment;
+
+pub fn define_natives(globals: &mut Environment) {
+    // lists and maps have a length too
+    globals.define_native("len", 1, |args| {
+        match &args[0] {
+            Value::Strng(s) => Ok(Value::from(s.chars().count() as f64)),
+            Value::List(items) => Ok(Value::from(items.borrow().len() as f64)),
+            Value::Map(entries) => Ok(Value::from(entries.borrow().len() as f64)),
+            Value::Range(start, end) => Ok(Value::from((end - start).max(0.0))),
+            _ => Err("Argument to len must be a string, a list, a map or a range.".into())
+        }
+    });
+    globals.define_native("charAt", 2, |args| {
+        let s = string(args, 0, "charAt")?;
+        let i = index(args, 1, "charAt")?;
+        match s.chars().nth(i) {
+            Some(c) => Ok(Value::from(c.to_string())),
+            None => Err("String index out of range.".into())
+        }
+    });
+    // substring(s, start, end), end exclusive
+    globals.define_native("substring", 3, |args| {
+        let s = string(args, 0, "substring")?;
+        let start = index(args, 1, "substring")?;
+        let end = index(args, 2, "substring")?;
+        if start > end || end > s.chars().count() {
+            return Err("String index out of range.".into());
+        }
+        Ok(Value::from(s.chars().skip(start).take(end - start).collect::<String>()))
+    });
+    // -1 when it isn't there
+    globals.define_native("indexOf", 2, |args| {
+        let s = string(args, 0, "indexOf")?;
+        let needle = string(args, 1, "indexOf")?;
+        let found = s.find(needle).map(|byte| s[..byte].chars().count() as f64);
+        Ok(Value::from(found.unwrap_or(-1.0)))
+    });
+    globals.define_native("contains", 2, |args| {
+        Ok(Value::from(string(args, 0, "contains")?.contains(string(args, 1, "contains")?)))
+    });
+    globals.define_native("startsWith", 2, |args| {
+        Ok(Value::from(string(args, 0, "startsWith")?.starts_with(string(args, 1, "startsWith")?)))
+    });
+    globals.define_native("endsWith", 2, |args| {
+        Ok(Value::from(string(args, 0, "endsWith")?.ends_with(string(args, 1, "endsWith")?)))
+    });
+    // replaces every occurrence
+    globals.define_native("replace", 3, |args| {
+        let s = string(args, 0, "replace")?;
+        let from = string(args, 1, "replace")?;
+        let to = string(args, 2, "replace")?;
+        if from.is_empty() {
+            return Err("Can't replace an empty string.".into());
+        }
+        Ok(Value::from(s.replace(from, to)))
+    });
+    // split(s, separator) into a list of strings
+    globals.define_native("split", 2, |args| {
+        let s = string(args, 0, "split")?;
+        let separator = string(args, 1, "split")?;
+        let parts: Vec<Value> = if separator.is_empty() {
+            // an empty separator splits into characters
+            s.chars().map(|c| Value::from(c.to_string())).collect()
+        } else {
+            s.split(separator).map(|part| Value::from(part.to_string())).collect()
+        };
+        Ok(Value::from(parts))
+    });
+    globals.define_native("upper", 1, |args| Ok(Value::from(string(args, 0, "upper")?.to_uppercase())));
+    globals.define_native("lower", 1, |args| Ok(Value::from(string(args, 0, "lower")?.to_lowercase())));
+    globals.define_native("trim", 1, |args| Ok(Value::from(string(args, 0, "trim")?.trim().to_string())));
+
+    // conversions
+    globals.define_native("toString", 1, |args| Ok(Value::from(format!("{}", args[0]))));
+    // nil when the string isn't a number
+    globals.define_native("parseNumber", 1, |args| {
+        match string(args, 0, "parseNumber")?.trim().parse::<f64>() {
+            Ok(n) => Ok(Value::from(n)),
+            Err(_) => Ok(Value::Nil)
+        }
+    });
+}
+
+// also used by the other natives that take strings
+pub fn string<'a>(args: &'a [Value], position: usize, name: &str) -> Result<&'a str, String> {
+    match &args[position] {
+        Value::Strng(s) => Ok(s),
+        _ => Err(format!("Argument {} to {} must be a string.", position + 1, name))
+    }
+}
+
+// a char position: a whole, non-negative number
+fn index(args: &[Value], position: usize, name: &str) -> Result<usize, String> {
+    match args[position] {
+        Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        _ => Err(format!("Argument {} to {} must be a non-negative integer.", position + 1, name))
+    }
+}
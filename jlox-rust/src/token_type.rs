@@ -3,14 +3,19 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
   // Single Character Tokens
-  LeftParen, RightParen, LeftBrace, RightBrace,
-  Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+  LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
+  Comma, Dot, Minus, Plus, Semicolon, Slash, Star, Colon, Question, Percent, Pipe,
 
   // One or two character tokens.
   Bang, BangEqual,
   Equal, EqualEqual,
   Greater, GreaterEqual,
   Less, LessEqual,
+  PlusEqual, PlusPlus,
+  MinusEqual, MinusMinus,
+  StarEqual, SlashEqual, PercentEqual,
+  StarStar, SlashSlash,
+  Arrow, DotDotDot,
 
   // Literals.
   Identifier,
@@ -20,6 +25,8 @@ pub enum TokenType {
   // Keywords.
   And, Class, Else, False, Fun, For, If, Nil, Or,
   Print, Return, Super, This, True, Var, While,
+  Break, Continue, Throw, Try, Catch, Finally, Const, Match, In,
+  Trait, With, Import,
 
   Eof
 }
@@ -0,0 +1,222 @@
+// runs the scripts under tests/lox and checks them against their comments,
+// like the craftinginterpreters test runner:
+//   // expect: <line>                  the next line the script prints
+//   // expect runtime error: <message> the first line of stderr, exiting with 70
+//   // expect trace: <line>            the next line of the stack trace after it
+//   // expect error: <message>         a line of stderr ending with this, exiting with 65
+//   // expect warning: <message>       a line of stderr ending with this
+//   // expect exit: <code>             the exit code, when the script calls exit
+//   // flags: <flags>                  passed to jlox before the script
+//   // args: <args>                    passed to the script after it
+//   // stdin: <line>                   a line of the script's stdin
+// each test below runs one directory of them. a script runs in an empty
+// directory of its own, so it can write files without tidying up after itself
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+
+struct Expected {
+    output: Vec<String>,
+    runtime_error: Option<String>,
+    trace: Vec<String>,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+    exit: Option<i32>,
+    flags: Vec<String>,
+    args: Vec<String>,
+    stdin: String,
+}
+
+fn parse(source: &str) -> Expected {
+    let mut expected = Expected {
+        output: vec![],
+        runtime_error: None,
+        trace: vec![],
+        errors: vec![],
+        warnings: vec![],
+        exit: None,
+        flags: vec![],
+        args: vec![],
+        stdin: String::new(),
+    };
+    for line in source.lines() {
+        // the last `// ` on the line, since `//` can also be floor division
+        let comment = match line.rfind("// ") {
+            Some(start) => &line[start + 3..],
+            None => continue,
+        };
+        if let Some(text) = comment.strip_prefix("expect: ") {
+            expected.output.push(text.to_string());
+        } else if let Some(text) = comment.strip_prefix("expect runtime error: ") {
+            expected.runtime_error = Some(text.to_string());
+        } else if let Some(text) = comment.strip_prefix("expect trace: ") {
+            expected.trace.push(text.to_string());
+        } else if let Some(text) = comment.strip_prefix("expect error: ") {
+            expected.errors.push(text.to_string());
+        } else if let Some(text) = comment.strip_prefix("expect warning: ") {
+            expected.warnings.push(text.to_string());
+        } else if let Some(text) = comment.strip_prefix("expect exit: ") {
+            expected.exit = Some(text.parse().unwrap());
+        } else if let Some(text) = comment.strip_prefix("flags: ") {
+            expected.flags.extend(text.split_whitespace().map(String::from));
+        } else if let Some(text) = comment.strip_prefix("args: ") {
+            expected.args.extend(text.split_whitespace().map(String::from));
+        } else if let Some(text) = comment.strip_prefix("stdin: ") {
+            expected.stdin.push_str(text);
+            expected.stdin.push('\n');
+        }
+    }
+    expected
+}
+
+// what's wrong with running one script, if anything
+fn check(path: &Path) -> Option<String> {
+    let source = fs::read_to_string(path).unwrap();
+    let expected = parse(&source);
+    let name = path.strip_prefix(env!("CARGO_MANIFEST_DIR")).unwrap().to_string_lossy().replace(['/', '.'], "-");
+    let cwd = std::env::temp_dir().join(format!("jlox{}-{}", process::id(), name));
+    fs::create_dir_all(&cwd).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jlox-rust"))
+        .args(&expected.flags)
+        .arg(path)
+        .args(&expected.args)
+        .current_dir(&cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(expected.stdin.as_bytes()).unwrap();
+    let result = child.wait_with_output().unwrap();
+    fs::remove_dir_all(&cwd).unwrap();
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    let output: Vec<&str> = stdout.lines().collect();
+    let code = result.status.code();
+
+    if output != expected.output {
+        return Some(format!("expected output {:?}\n  got {:?}\n  stderr: {}", expected.output, output, stderr));
+    }
+    let expected_code = if let Some(code) = expected.exit {
+        code
+    } else if !expected.errors.is_empty() {
+        65
+    } else if expected.runtime_error.is_some() {
+        70
+    } else {
+        0
+    };
+    if code != Some(expected_code) {
+        return Some(format!("expected exit code {}, got {:?}\n  stderr: {}", expected_code, code, stderr));
+    }
+    if let Some(message) = &expected.runtime_error {
+        if stderr.lines().next() != Some(message.as_str()) {
+            return Some(format!("expected runtime error {:?}\n  got {}", message, stderr));
+        }
+        let trace: Vec<&str> = stderr.lines().skip(1).collect();
+        if !expected.trace.is_empty() && trace != expected.trace {
+            return Some(format!("expected trace {:?}\n  got {:?}", expected.trace, trace));
+        }
+    }
+    for message in expected.errors.iter().chain(expected.warnings.iter()) {
+        if !stderr.lines().any(|line| line.ends_with(message.as_str())) {
+            return Some(format!("expected {:?} in stderr\n  got {}", message, stderr));
+        }
+    }
+    None
+}
+
+fn run_dir(dir: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox").join(dir);
+    let mut paths: Vec<_> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scripts in {}", dir.display());
+
+    let failures: Vec<String> = paths.iter()
+        .filter_map(|path| check(path).map(|failure| format!("{}: {}", path.display(), failure)))
+        .collect();
+    if !failures.is_empty() {
+        panic!("\n{}", failures.join("\n"));
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json() { run_dir("json"); }
+
+#[test]
+fn limits() { run_dir("limits"); }
+
+#[test]
+fn depth() { run_dir("depth"); }
+
+#[test]
+fn trace() { run_dir("trace"); }
+
+#[test]
+fn math() { run_dir("math"); }
+
+#[test]
+fn strings() { run_dir("strings"); }
+
+#[test]
+fn io() { run_dir("io"); }
+
+#[test]
+fn args() { run_dir("args"); }
+
+#[test]
+fn lists() { run_dir("lists"); }
+
+#[test]
+fn maps() { run_dir("maps"); }
+
+#[test]
+fn control_flow() { run_dir("control_flow"); }
+
+#[test]
+fn conditional() { run_dir("conditional"); }
+
+#[test]
+fn assignment() { run_dir("assignment"); }
+
+#[test]
+fn operators() { run_dir("operators"); }
+
+#[test]
+fn functions() { run_dir("functions"); }
+
+#[test]
+fn parameters() { run_dir("parameters"); }
+
+#[test]
+fn exceptions() { run_dir("exceptions"); }
+
+#[test]
+fn constants() { run_dir("constants"); }
+
+#[test]
+fn strict() { run_dir("strict"); }
+
+#[test]
+fn match_statement() { run_dir("match"); }
+
+#[test]
+fn for_in() { run_dir("for_in"); }
+
+#[test]
+fn classes() { run_dir("classes"); }
+
+#[test]
+fn special_methods() { run_dir("special_methods"); }
+
+#[test]
+fn traits() { run_dir("traits"); }
+
+// the modules they import are in modules/lib, which run_dir doesn't look in
+#[test]
+fn modules() { run_dir("modules"); }